@@ -0,0 +1,191 @@
+use std::ops::{Add, Mul, Sub};
+use crate::color::Vec3;
+use crate::daycycle::parse_hours;
+
+// Cómo se llega de un cuadro clave al siguiente.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Interp {
+    Linear,
+    CatmullRom,
+    Bezier(f32), // curva de Bézier con asas del largo de la tangente de Catmull-Rom por este factor
+}
+
+fn parse_interp(s: &str) -> Option<Interp> {
+    match s {
+        "linear" => Some(Interp::Linear),
+        "catmull-rom" | "catmull" | "spline" => Some(Interp::CatmullRom),
+        "bezier" => Some(Interp::Bezier(0.5)),
+        _ => None,
+    }
+}
+
+// Valores en el tiempo; cada clave guarda la interpolación del tramo que empieza en ella.
+struct Track<T> {
+    keys: Vec<(f32, T, Interp)>,
+}
+
+impl<T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>> Track<T> {
+    fn new() -> Self { Self { keys: Vec::new() } }
+
+    fn sample(&self, t: f32) -> Option<T> {
+        let keys = &self.keys;
+        let (first, last) = (keys.first()?, keys.last()?);
+        if t <= first.0 { return Some(first.1); }
+        if t >= last.0 { return Some(last.1); }
+        let i = keys.iter().rposition(|k| k.0 <= t)?;
+        let (t1, p1, interp) = keys[i];
+        let (t2, p2, _) = keys[i + 1];
+        let s = (t - t1) / (t2 - t1);
+        if interp == Interp::Linear { return Some(p1 + (p2 - p1) * s); }
+        // tangentes de Catmull-Rom escaladas al largo del tramo (las claves no tienen por qué estar
+        // equiespaciadas); en los extremos se repite la clave
+        let (t0, p0, _) = keys[i.saturating_sub(1)];
+        let (t3, p3, _) = keys[(i + 2).min(keys.len() - 1)];
+        let m1 = if t2 > t0 { (p2 - p0) * ((t2 - t1) / (t2 - t0)) } else { p2 - p1 };
+        let m2 = if t3 > t1 { (p3 - p1) * ((t2 - t1) / (t3 - t1)) } else { p2 - p1 };
+        let handle = match interp { Interp::Bezier(k) => k, _ => 1.0 };
+        // Bézier cúbica; con asas de 1/3 de la tangente es la misma curva que Catmull-Rom
+        let (b1, b2) = (p1 + m1 * (handle / 3.0), p2 - m2 * (handle / 3.0));
+        let u = 1.0 - s;
+        Some(p1 * (u * u * u) + b1 * (3.0 * u * u * s) + b2 * (3.0 * u * s * s) + p2 * (s * s * s))
+    }
+}
+
+// Lo que la animación dice de un instante; `None` = esa pista no tiene claves.
+pub struct CameraPose {
+    pub position: Option<Vec3>,
+    pub target: Option<Vec3>,
+    pub fov: Option<f32>,
+    pub hours: Option<f32>,
+}
+
+// Pistas de cámara y de hora leídas de un archivo de texto:
+//
+//   interpolation = catmull-rom
+//   0    position=12,8,0  target=0,4,0  fov=60  time=9:00
+//   4.5  position=0,10,12 interp=linear
+//   9    position=-12,8,0 time=12:30
+//
+// Cada línea de clave empieza con su tiempo en segundos; los campos que falten no tocan esa pista.
+pub struct CameraAnimation {
+    position: Track<Vec3>,
+    target: Track<Vec3>,
+    fov: Track<f32>,
+    hours: Track<f32>,
+    pub duration: f32,
+}
+
+fn parse_vec3(s: &str) -> Option<Vec3> {
+    let parts: Vec<f32> = s.split(',').map(|p| p.trim().parse().ok()).collect::<Option<_>>()?;
+    if parts.len() != 3 { return None; }
+    Some(Vec3::new(parts[0], parts[1], parts[2]))
+}
+
+impl CameraAnimation {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut anim = Self { position: Track::new(), target: Track::new(), fov: Track::new(), hours: Track::new(), duration: 0.0 };
+        let mut default_interp = Interp::CatmullRom;
+        for (n, line) in text.lines().enumerate() {
+            let err = |msg: String| format!("{}:{}: {}", path, n + 1, msg);
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() { continue; }
+            if let Some(v) = line.strip_prefix("interpolation").and_then(|r| r.trim_start().strip_prefix('=')) {
+                default_interp = parse_interp(v.trim()).ok_or_else(|| err(format!("unknown interpolation '{}'", v.trim())))?;
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let t: f32 = fields.next().and_then(|t| t.parse().ok()).ok_or_else(|| err("expected a time in seconds".into()))?;
+            if t < anim.duration { return Err(err(format!("keyframe at {}s comes before the previous one", t))); }
+            anim.duration = t;
+            let mut interp = default_interp;
+            let mut values = Vec::new();
+            for field in fields {
+                let (k, v) = field.split_once('=').ok_or_else(|| err(format!("expected key=value, got '{}'", field)))?;
+                match k {
+                    "interp" => interp = parse_interp(v).ok_or_else(|| err(format!("unknown interpolation '{}'", v)))?,
+                    "handle" => {
+                        let h: f32 = v.parse().map_err(|_| err(format!("bad handle '{}'", v)))?;
+                        interp = Interp::Bezier(h.max(0.0));
+                    }
+                    _ => values.push((k, v)),
+                }
+            }
+            for (k, v) in values {
+                let bad = || err(format!("bad value for {}: '{}'", k, v));
+                match k {
+                    "position" => anim.position.keys.push((t, parse_vec3(v).ok_or_else(bad)?, interp)),
+                    "target" => anim.target.keys.push((t, parse_vec3(v).ok_or_else(bad)?, interp)),
+                    "fov" => anim.fov.keys.push((t, v.parse().map_err(|_| bad())?, interp)),
+                    "time" => anim.hours.keys.push((t, parse_hours(v).ok_or_else(bad)?, interp)),
+                    _ => return Err(err(format!("unknown track '{}'", k))),
+                }
+            }
+        }
+        Ok(anim)
+    }
+
+    pub fn sample(&self, t: f32) -> CameraPose {
+        CameraPose { position: self.position.sample(t), target: self.target.sample(t), fov: self.fov.sample(t), hours: self.hours.sample(t) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(name: &str, text: &str) -> Result<CameraAnimation, String> {
+        let path = std::env::temp_dir().join(format!("proyecto2-{}-{}.anim", name, std::process::id()));
+        std::fs::write(&path, text).unwrap();
+        let anim = CameraAnimation::load(path.to_str().unwrap());
+        let _ = std::fs::remove_file(&path);
+        anim
+    }
+
+    #[test]
+    fn parses_tracks_and_interpolates_linearly() {
+        let anim = load("linear", "interpolation = linear\n0 position=0,0,0 fov=60 time=9:00  # inicio\n\n2 position=4,2,-2 fov=40\n4 time=12:30\n").unwrap();
+        assert_eq!(anim.duration, 4.0);
+        let pose = anim.sample(1.0);
+        let p = pose.position.unwrap();
+        assert_eq!((p.x, p.y, p.z), (2.0, 1.0, -1.0));
+        assert_eq!(pose.fov, Some(50.0));
+        assert_eq!(pose.hours, Some(9.0 + 3.5 / 4.0));
+        assert!(pose.target.is_none());
+        // fuera del rango de claves se mantiene la del extremo
+        assert_eq!(anim.sample(-1.0).fov, Some(60.0));
+        assert_eq!(anim.sample(3.0).fov, Some(40.0));
+    }
+
+    #[test]
+    fn splines_pass_through_the_keys() {
+        let anim = load("spline", "0 fov=10\n1 fov=30\n3 fov=20 interp=bezier\n4 fov=50 handle=2\n6 fov=0\n").unwrap();
+        for (t, fov) in [(0.0, 10.0), (1.0, 30.0), (3.0, 20.0), (4.0, 50.0), (6.0, 0.0)] {
+            assert!((anim.sample(t).fov.unwrap() - fov).abs() < 1e-5, "t = {}", t);
+        }
+        // claves equiespaciadas sobre una recta: Catmull-Rom no se aparta de ella
+        let line = load("straight", "0 fov=10\n1 fov=20\n2 fov=30\n3 fov=40\n").unwrap();
+        assert!((line.sample(1.5).fov.unwrap() - 25.0).abs() < 1e-4);
+        // asas de 1 (un tercio de la tangente) son la misma curva que Catmull-Rom
+        let catmull = load("catmull", "0 fov=10\n1 fov=40\n2 fov=15\n3 fov=20\n").unwrap();
+        let bezier = load("bezier", "0 fov=10 handle=1\n1 fov=40 handle=1\n2 fov=15 handle=1\n3 fov=20\n").unwrap();
+        for t in [0.3, 1.2, 1.7, 2.5] {
+            assert!((catmull.sample(t).fov.unwrap() - bezier.sample(t).fov.unwrap()).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn reports_errors_with_the_line_number() {
+        for (text, line, msg) in [
+            ("0 fov=60\n2 fov=50\n1 fov=40\n", 3, "comes before"),
+            ("0 zoom=2\n", 1, "unknown track"),
+            ("0 position=1,2\n", 1, "bad value"),
+            ("interpolation = cubic\n", 1, "unknown interpolation"),
+            ("# sin tiempo\nfov=60\n", 2, "expected a time"),
+            ("0 fov 60\n", 1, "expected key=value"),
+        ] {
+            let err = load("errors", text).err().unwrap();
+            assert!(err.contains(&format!(".anim:{}: ", line)) && err.contains(msg), "{}", err);
+        }
+    }
+}
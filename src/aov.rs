@@ -0,0 +1,115 @@
+use crate::color::{Color, Vec3};
+use crate::config::Config;
+use crate::exr::write_exr;
+use crate::hdr::write_hdr;
+
+// Lo que ve el rayo primario de un píxel, además del color final. Con varios rayos por píxel
+// (profundidad de campo) se guarda el primero, para que ids y coordenadas no se mezclen; solo la
+// luz directa se promedia entre todos, igual que el color.
+#[derive(Clone, Copy)]
+pub struct AovSample {
+    pub depth: f32,     // distancia al primer impacto; MISS_DEPTH si da al cielo
+    pub normal: Vec3,   // normal de sombreado en espacio de mundo
+    pub albedo: Color,
+    pub uv: (f32, f32),
+    pub material: f32,  // índice de MaterialKind + 1 (0 = cielo)
+    pub voxel: Vec3,    // coordenadas enteras del vóxel impactado
+    pub shadow: f32,    // 1 = la luz principal no llega
+    pub direct: Color,  // luz directa del sol o la luna en la superficie, atenuada por niebla, nubes y agua como el color
+    pub position: Vec3, // punto de impacto en el mundo (para reproyectar entre cuadros, no es un pase)
+}
+
+pub const MISS_DEPTH: f32 = 1e10;
+
+impl Default for AovSample {
+    fn default() -> Self {
+        Self { depth: MISS_DEPTH, normal: Vec3::new(0.0, 0.0, 0.0), albedo: Color::black(), uv: (0.0, 0.0), material: 0.0,
+            voxel: Vec3::new(0.0, 0.0, 0.0), shadow: 0.0, direct: Color::black(), position: Vec3::new(0.0, 0.0, 0.0) }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Pass { Depth, Normal, Albedo, Uv, Material, Voxel, Shadow, Direct, Indirect }
+
+const ALL: [Pass; 9] = [Pass::Depth, Pass::Normal, Pass::Albedo, Pass::Uv, Pass::Material, Pass::Voxel, Pass::Shadow, Pass::Direct, Pass::Indirect];
+
+impl Pass {
+    pub fn name(self) -> &'static str {
+        match self {
+            Pass::Depth => "depth", Pass::Normal => "normal", Pass::Albedo => "albedo", Pass::Uv => "uv",
+            Pass::Material => "material", Pass::Voxel => "voxel", Pass::Shadow => "shadow",
+            Pass::Direct => "direct", Pass::Indirect => "indirect",
+        }
+    }
+
+    // Nombres de canal (sufijos) del pase en EXR.
+    fn channels(self) -> &'static [&'static str] {
+        match self {
+            Pass::Depth | Pass::Material | Pass::Shadow => &["Y"],
+            Pass::Uv => &["U", "V"],
+            Pass::Normal | Pass::Voxel => &["X", "Y", "Z"],
+            Pass::Albedo | Pass::Direct | Pass::Indirect => &["R", "G", "B"],
+        }
+    }
+
+    // Valores del pase para un píxel. `beauty` hace falta para la luz indirecta, que es todo lo
+    // que no es directa.
+    fn values(self, s: &AovSample, beauty: Color) -> [f32; 3] {
+        let rgb = |c: Color| [c.r, c.g, c.b];
+        match self {
+            Pass::Depth => [s.depth, 0.0, 0.0],
+            Pass::Normal => [s.normal.x, s.normal.y, s.normal.z],
+            Pass::Albedo => rgb(s.albedo),
+            Pass::Uv => [s.uv.0, s.uv.1, 0.0],
+            Pass::Material => [s.material, 0.0, 0.0],
+            Pass::Voxel => [s.voxel.x, s.voxel.y, s.voxel.z],
+            Pass::Shadow => [s.shadow, 0.0, 0.0],
+            Pass::Direct => rgb(s.direct),
+            Pass::Indirect => rgb(beauty - s.direct),
+        }
+    }
+}
+
+// `--aovs depth,normal,albedo` o `--aovs all`.
+pub fn passes_from_config(cfg: &Config) -> Vec<Pass> {
+    let Some(list) = cfg.get_str("aovs") else { return Vec::new(); };
+    if list == "all" { return ALL.to_vec(); }
+    list.split(',').map(str::trim).filter(|n| !n.is_empty()).filter_map(|name| {
+        let pass = ALL.iter().copied().find(|p| p.name() == name);
+        if pass.is_none() { eprintln!("[aov] unknown pass '{}'", name); }
+        pass
+    }).collect()
+}
+
+// Escribe la imagen lineal (sin mapeo de tonos) y sus pases. En EXR todo va en un solo archivo
+// multicanal (R, G, B y `pase.canal`); en .hdr cada pase va aparte, como `nombre.pase.hdr`.
+pub fn write_float_outputs(path: &str, width: u32, height: u32, beauty: &[Color], aovs: &[AovSample], passes: &[Pass]) -> Result<(), String> {
+    let is_exr = path.to_ascii_lowercase().ends_with(".exr");
+    if is_exr {
+        let mut channels: Vec<(String, Vec<f32>)> = vec![
+            ("R".into(), beauty.iter().map(|c| c.r).collect()),
+            ("G".into(), beauty.iter().map(|c| c.g).collect()),
+            ("B".into(), beauty.iter().map(|c| c.b).collect()),
+        ];
+        for &pass in passes {
+            for (k, suffix) in pass.channels().iter().enumerate() {
+                // la profundidad va en el canal Z estándar
+                let name = if pass == Pass::Depth { "Z".to_string() } else { format!("{}.{}", pass.name(), suffix) };
+                channels.push((name, aovs.iter().zip(beauty).map(|(s, &b)| pass.values(s, b)[k]).collect()));
+            }
+        }
+        return write_exr(path, width, height, &channels);
+    }
+    write_hdr(path, width, height, beauty)?;
+    let stem = path.rsplit_once('.').map_or(path, |(stem, _)| stem);
+    for &pass in passes {
+        let data: Vec<Color> = aovs.iter().zip(beauty).map(|(s, &b)| {
+            let v = pass.values(s, b);
+            // RGBE no guarda negativos: las normales van como n * 0.5 + 0.5; los pases de un canal, en gris
+            if pass == Pass::Normal { Color::new(v[0] * 0.5 + 0.5, v[1] * 0.5 + 0.5, v[2] * 0.5 + 0.5) }
+            else if pass.channels().len() == 1 { Color::new(v[0], v[0], v[0]) } else { Color::new(v[0], v[1], v[2]) }
+        }).collect();
+        write_hdr(&format!("{}.{}.hdr", stem, pass.name()), width, height, &data)?;
+    }
+    Ok(())
+}
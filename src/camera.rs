@@ -1,5 +1,184 @@
 use crate::color::Vec3;
-use crate::ray_intersect::Ray;
+use crate::config::Config;
+use crate::ray_intersect::{Ray, SceneObject};
+use crate::voxel_world::VoxelWorld;
+
+// Lo común a las cámaras: dónde está y hacia dónde mira; el rayo sale de ahí.
+pub trait Camera: Sync {
+    fn position(&self) -> Vec3;
+    // (adelante, derecha, arriba), ortonormales
+    fn basis(&self) -> (Vec3, Vec3, Vec3);
+
+    // `lens_uv` en [0,1)² elige el punto de la apertura (solo importa con profundidad de campo).
+    fn generate_ray(&self, lens: &Lens, u: f32, v: f32, aspect: f32, lens_uv: (f32, f32)) -> Ray {
+        // u,v en [0,1]; con estéreo cada ojo ocupa media imagen (izquierdo a la izquierda o arriba)
+        let (u, v, aspect, eye) = match lens.stereo {
+            Stereo::Off => (u, v, aspect, 0.0),
+            Stereo::SideBySide => if u < 0.5 { (u * 2.0, v, aspect * 0.5, -0.5) } else { (u * 2.0 - 1.0, v, aspect * 0.5, 0.5) },
+            Stereo::OverUnder => if v < 0.5 { (u, v * 2.0, aspect * 2.0, -0.5) } else { (u, v * 2.0 - 1.0, aspect * 2.0, 0.5) },
+        };
+        let eye = eye * lens.eye_separation;
+        let (sx, sy) = (2.0 * u - 1.0, 1.0 - 2.0 * v);
+        let (forward, right, up) = self.basis();
+        let pos = self.position() + right * eye;
+        match lens.projection {
+            Projection::Orthographic => {
+                // rayos paralelos que salen del plano de la cámara
+                let h = lens.ortho_size * 0.5;
+                let origin = pos + right * (sx * h * aspect) + up * (sy * h);
+                return Ray { origin, dir: forward };
+            }
+            Projection::Equirectangular | Projection::Cubemap => {
+                let dir = if lens.projection == Projection::Equirectangular {
+                    // misma convención que los panoramas HDR de `--env`
+                    let lon = (u - 0.5) * std::f32::consts::TAU;
+                    let lat = (0.5 - v) * std::f32::consts::PI;
+                    Vec3::new(lat.cos() * lon.cos(), lat.sin(), lat.cos() * lon.sin())
+                } else {
+                    cube_face_dir(u, sy)
+                };
+                // estéreo omnidireccional: cada ojo se corre a un lado de la dirección horizontal
+                // del rayo, menos cuanto más se mira hacia arriba o abajo
+                let origin = self.position() + Vec3::new(-dir.z, 0.0, dir.x) * eye;
+                return Ray { origin, dir };
+            }
+            Projection::Perspective => {}
+        }
+        let t = (lens.fov.to_radians() * 0.5).tan();
+        let dir = (forward + right * (sx * aspect * t) + up * (sy * t)).normalized();
+        if lens.aperture <= 0.0 { return Ray { origin: pos, dir }; }
+        // lente delgada: todos los rayos del píxel pasan por el mismo punto del plano de enfoque
+        let focus = pos + dir * (lens.focus_distance / dir.dot(forward));
+        let r = lens.aperture * lens_uv.0.sqrt();
+        let phi = std::f32::consts::TAU * lens_uv.1;
+        let origin = pos + right * (r * phi.cos()) + up * (r * phi.sin());
+        Ray { origin, dir: (focus - origin).normalized() }
+    }
+}
+
+// Caras del cubemap en una tira horizontal, orden +X, -X, +Y, -Y, +Z, -Z (convención de OpenGL).
+// `b` es la coordenada vertical de la cara, +1 arriba.
+fn cube_face_dir(u: f32, b: f32) -> Vec3 {
+    let face = ((u * 6.0) as i32).clamp(0, 5);
+    let a = 2.0 * (u * 6.0 - face as f32) - 1.0;
+    let d = match face {
+        0 => Vec3::new(1.0, b, -a),
+        1 => Vec3::new(-1.0, b, a),
+        2 => Vec3::new(a, 1.0, -b),
+        3 => Vec3::new(a, -1.0, b),
+        4 => Vec3::new(a, b, 1.0),
+        _ => Vec3::new(-a, b, -1.0),
+    };
+    d.normalized()
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Projection { Perspective, Orthographic, Equirectangular, Cubemap }
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Stereo { Off, SideBySide, OverUnder }
+
+// Óptica de la cámara, compartida por la órbita y la cámara libre.
+#[derive(Clone)]
+pub struct Lens {
+    pub projection: Projection,
+    pub isometric: bool,     // ortográfica con el ángulo clásico (45° de giro, 35.26° de inclinación)
+    pub fov: f32,            // campo de visión vertical, en grados
+    pub ortho_size: f32,     // alto visible en vóxeles con proyección ortográfica
+    pub aperture: f32,       // radio de la apertura; 0 = estenopeica, todo enfocado
+    pub focus_distance: f32,
+    pub autofocus: bool,     // enfoca cada cuadro lo que está en el centro de la pantalla
+    pub samples: u32,        // rayos por píxel cuando hay desenfoque
+    pub stereo: Stereo,
+    pub eye_separation: f32, // distancia entre ojos, en vóxeles
+}
+
+impl Lens {
+    pub fn from_config(cfg: &Config) -> Self {
+        let (projection, isometric) = match cfg.get_str("projection").unwrap_or("perspective") {
+            "ortho" | "orthographic" => (Projection::Orthographic, false),
+            "iso" | "isometric" => (Projection::Orthographic, true),
+            "equirect" | "equirectangular" | "360" => (Projection::Equirectangular, false),
+            "cubemap" | "cube" => (Projection::Cubemap, false),
+            other => {
+                if other != "perspective" { eprintln!("[camera] unknown projection '{}', using perspective", other); }
+                (Projection::Perspective, false)
+            }
+        };
+        let focus = cfg.get_str("focus").unwrap_or("auto");
+        let stereo = match cfg.get_str("stereo").unwrap_or("off") {
+            "sbs" | "side-by-side" => Stereo::SideBySide,
+            "ou" | "over-under" | "top-bottom" => Stereo::OverUnder,
+            other => {
+                if other != "off" && other != "false" { eprintln!("[camera] unknown stereo layout '{}', using mono", other); }
+                Stereo::Off
+            }
+        };
+        Self {
+            projection, isometric,
+            fov: cfg.get_f32("fov", 60.0).clamp(1.0, 170.0),
+            ortho_size: cfg.get_f32("ortho_size", 18.0).max(0.1),
+            aperture: cfg.get_f32("aperture", 0.0).max(0.0),
+            focus_distance: if focus == "auto" { 10.0 } else { cfg.get_f32("focus", 10.0).max(0.01) },
+            autofocus: focus == "auto",
+            samples: cfg.get_u32("dof_samples", 8).max(1),
+            stereo,
+            eye_separation: cfg.get_f32("eye_separation", 0.065),
+        }
+    }
+
+    // Tamaño de imagen por defecto para el render sin ventana: `height` es el alto de un ojo
+    // (o de una cara del cubemap) y el ancho sale de la proyección.
+    pub fn image_size(&self, width: Option<u32>, height: u32) -> (u32, u32) {
+        let (w, h) = match self.projection {
+            Projection::Equirectangular => (height * 2, height),
+            Projection::Cubemap => (height * 6, height),
+            _ => (width.unwrap_or(height * 4 / 3), height),
+        };
+        match self.stereo {
+            Stereo::Off => (w, h),
+            Stereo::SideBySide => (w * 2, h),
+            Stereo::OverUnder => (w, h * 2),
+        }
+    }
+
+    // Lo inverso de `generate_ray`: dónde cae el punto `p` en la imagen ([0,1]²) vista desde
+    // `position` con `basis`. Solo en perspectiva u ortográfica sin estéreo; si no, o si el punto
+    // queda detrás de la cámara, None.
+    pub fn project(&self, position: Vec3, basis: (Vec3, Vec3, Vec3), p: Vec3, aspect: f32) -> Option<(f32, f32)> {
+        if self.stereo != Stereo::Off { return None; }
+        let (forward, right, up) = basis;
+        let d = p - position;
+        let (sx, sy) = match self.projection {
+            Projection::Perspective => {
+                let z = d.dot(forward);
+                if z <= 1e-4 { return None; }
+                let t = (self.fov.to_radians() * 0.5).tan();
+                (d.dot(right) / (z * aspect * t), d.dot(up) / (z * t))
+            }
+            Projection::Orthographic => {
+                let h = self.ortho_size * 0.5;
+                (d.dot(right) / (h * aspect), d.dot(up) / h)
+            }
+            _ => return None,
+        };
+        Some(((sx + 1.0) * 0.5, (1.0 - sy) * 0.5))
+    }
+
+    // Rayos por píxel que hacen falta con esta lente.
+    pub fn rays_per_pixel(&self) -> u32 {
+        if self.projection == Projection::Perspective && self.aperture > 0.0 { self.samples } else { 1 }
+    }
+
+    // Distancia de enfoque según lo que ve el rayo central; si no ve nada, se queda como estaba.
+    pub fn autofocus(&mut self, camera: &dyn Camera, world: &VoxelWorld) {
+        if !self.autofocus || self.aperture <= 0.0 { return; }
+        let (forward, _, _) = camera.basis();
+        if let Some(hit) = world.intersect(&Ray { origin: camera.position(), dir: forward }) {
+            self.focus_distance = hit.t.max(0.05);
+        }
+    }
+}
 
 pub struct OrbitCamera {
     target: Vec3,
@@ -22,25 +201,89 @@ impl OrbitCamera {
         self.pitch = (self.pitch + dpitch).clamp(-1.2, 1.2);
     }
     pub fn zoom_mul(&mut self, factor: f32) { self.radius = (self.radius * factor).clamp(5.0, 25.0); }
+    pub fn set_radius(&mut self, radius: f32) { self.radius = radius.clamp(5.0, 25.0); }
 
-    pub fn position(&self) -> Vec3 {
+    fn orbit_position(&self) -> Vec3 {
         let x = self.radius * self.yaw.cos() * self.pitch.cos();
         let y = self.radius * self.pitch.sin();
         let z = self.radius * self.yaw.sin() * self.pitch.cos();
         Vec3::new(x, y, z) + self.target
     }
+}
+
+fn look_at(from: Vec3, to: Vec3) -> (Vec3, Vec3, Vec3) {
+    let forward = (to - from).normalized();
+    let world_up = Vec3::new(0.0, 1.0, 0.0);
+    let right = forward.cross(world_up).normalized();
+    let up = right.cross(forward).normalized();
+    (forward, right, up)
+}
+
+impl Camera for OrbitCamera {
+    fn position(&self) -> Vec3 { self.orbit_position() }
+    fn basis(&self) -> (Vec3, Vec3, Vec3) { look_at(self.orbit_position(), self.target) }
+}
+
+// Cámara que mira a un punto desde cualquier posición (la mueven las animaciones).
+pub struct LookAtCamera {
+    pub position: Vec3,
+    pub target: Vec3,
+}
+
+impl Camera for LookAtCamera {
+    fn position(&self) -> Vec3 { self.position }
+    fn basis(&self) -> (Vec3, Vec3, Vec3) { look_at(self.position, self.target) }
+}
+
+// Cámara con posición y ejes ya resueltos, como llegan en un trabajo de render distribuido.
+pub struct FixedCamera {
+    pub position: Vec3,
+    pub basis: (Vec3, Vec3, Vec3),
+}
+
+impl Camera for FixedCamera {
+    fn position(&self) -> Vec3 { self.position }
+    fn basis(&self) -> (Vec3, Vec3, Vec3) { self.basis }
+}
+
+// Cámara libre en primera persona: se mueve en sus propios ejes, mira con yaw/pitch y puede girar (roll).
+pub struct FlyCamera {
+    pub position: Vec3,
+    yaw: f32,
+    pitch: f32,
+    roll: f32,
+    pub speed: f32, // vóxeles por segundo
+}
+
+impl FlyCamera {
+    // Arranca donde está la órbita, mirando a lo mismo.
+    pub fn from_orbit(orbit: &OrbitCamera) -> Self {
+        let (forward, _, _) = orbit.basis();
+        Self { position: orbit.position(), yaw: forward.z.atan2(forward.x), pitch: forward.y.clamp(-1.0, 1.0).asin(), roll: 0.0, speed: 6.0 }
+    }
+
+    pub fn look(&mut self, dyaw: f32, dpitch: f32) {
+        self.yaw += dyaw;
+        self.pitch = (self.pitch + dpitch).clamp(-1.55, 1.55);
+    }
+    pub fn roll_delta(&mut self, d: f32) { self.roll = (self.roll + d).rem_euclid(std::f32::consts::TAU); }
+    pub fn speed_mul(&mut self, factor: f32) { self.speed = (self.speed * factor).clamp(0.25, 200.0); }
+
+    // Desplazamiento en ejes locales (adelante, derecha, arriba), escalado por la velocidad y dt.
+    pub fn fly(&mut self, forward: f32, right: f32, up: f32, dt: f32) {
+        let (f, r, u) = self.basis();
+        let step = f * forward + r * right + u * up;
+        if step.length() > 0.0 { self.position += step.normalized() * (self.speed * dt); }
+    }
+}
 
-    pub fn generate_ray(&self, u: f32, v: f32, aspect: f32) -> Ray {
-        // u,v en [0,1]
-        let fov = 60.0_f32.to_radians();
-        let px = (2.0 * u - 1.0) * aspect * (fov * 0.5).tan();
-        let py = (1.0 - 2.0 * v) * (fov * 0.5).tan();
-        let cam_pos = self.position();
-        let forward = (self.target - cam_pos).normalized();
-        let world_up = Vec3::new(0.0, 1.0, 0.0);
-        let right = forward.cross(world_up).normalized();
+impl Camera for FlyCamera {
+    fn position(&self) -> Vec3 { self.position }
+    fn basis(&self) -> (Vec3, Vec3, Vec3) {
+        let forward = Vec3::new(self.yaw.cos() * self.pitch.cos(), self.pitch.sin(), self.yaw.sin() * self.pitch.cos());
+        let right = forward.cross(Vec3::new(0.0, 1.0, 0.0)).normalized();
         let up = right.cross(forward).normalized();
-        let dir = (forward + right * px + up * py).normalized();
-        Ray { origin: cam_pos, dir }
+        let (s, c) = self.roll.sin_cos();
+        (forward, right * c + up * s, up * c - right * s)
     }
 }
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use rayon::prelude::*;
+use crate::color::{Color, Vec3};
+use crate::config::Config;
+use crate::material::MaterialKind;
+use crate::ray_intersect::{HitInfo, Ray, SceneObject};
+use crate::sampling::Sampler;
+use crate::voxel_world::VoxelWorld;
+use crate::water::WaterMedium;
+
+const EPS: f32 = 4e-4;
+// los fotones usan secuencias aparte de las de los píxeles con la misma semilla
+const PHOTON_STREAM: u64 = 0x7068_6f74_6f6e;
+
+struct Photon { position: Vec3, dir: Vec3, weight: Color }
+
+// Mapa de fotones de cáusticas: luz de la fuente principal que pasó por agua o vidrio antes
+// de caer en una superficie difusa. Los pesos son relativos al color de la luz, así que el
+// resultado de `gather` se usa igual que `n·l` en el sombreado directo.
+pub struct CausticMap {
+    enabled: bool,
+    photon_count: u32,
+    preview_count: u32, // fotones mientras se navega en la ventana
+    preview: bool,
+    radius: f32,
+    photons: Vec<Photon>,
+    grid: HashMap<(i32, i32, i32), Vec<u32>>,
+    photon_area: f32, // área perpendicular a la luz que representa cada fotón
+    interval: f32,    // segundos entre reconstrucciones por el movimiento de las olas (0 = nunca)
+    key: Option<(u64, [i32; 3], u64, u32, u64)>, // revisión del mundo, dirección de la luz, paso de las olas, fotones y semilla con que se construyó
+}
+
+// Refracta `d` en una superficie con normal `n` (hacia el lado de donde viene `d`).
+// Devuelve la dirección transmitida y la fracción transmitida por Fresnel (Schlick).
+fn refract(d: Vec3, n: Vec3, eta: f32) -> Option<(Vec3, f32)> {
+    let cos_i = (-d.dot(n)).clamp(0.0, 1.0);
+    let k = 1.0 - eta * eta * (1.0 - cos_i * cos_i);
+    if k < 0.0 { return None; }
+    let cos_t = k.sqrt();
+    let r0 = ((1.0 - eta) / (1.0 + eta)).powi(2);
+    let c = 1.0 - if eta > 1.0 { cos_t } else { cos_i };
+    let fresnel = r0 + (1.0 - r0) * c.powi(5);
+    Some(((d * eta + n * (eta * cos_i - cos_t)).normalized(), 1.0 - fresnel))
+}
+
+// Distancia hasta salir del vóxel que contiene `p` y la normal de la cara de salida.
+fn voxel_exit(p: Vec3, d: Vec3, cell: Vec3) -> (f32, Vec3) {
+    let axis = |p: f32, d: f32, c: f32| if d > 0.0 { (c + 0.5 - p) / d } else if d < 0.0 { (c - 0.5 - p) / d } else { f32::INFINITY };
+    let (tx, ty, tz) = (axis(p.x, d.x, cell.x), axis(p.y, d.y, cell.y), axis(p.z, d.z, cell.z));
+    if tx <= ty && tx <= tz { (tx, Vec3::new(d.x.signum(), 0.0, 0.0)) }
+    else if ty <= tz { (ty, Vec3::new(0.0, d.y.signum(), 0.0)) }
+    else { (tz, Vec3::new(0.0, 0.0, d.z.signum())) }
+}
+
+impl CausticMap {
+    pub fn from_config(cfg: &Config) -> Self {
+        Self {
+            enabled: cfg.get_bool("caustics", true),
+            photon_count: cfg.get_u32("caustic_photons", 200_000),
+            preview_count: cfg.get_u32("caustic_preview_photons", 50_000),
+            preview: false,
+            radius: cfg.get_f32("caustic_radius", 0.15).max(0.01),
+            interval: cfg.get_f32("caustic_interval", 0.0).max(0.0),
+            photons: Vec::new(),
+            grid: HashMap::new(),
+            photon_area: 0.0,
+            key: None,
+        }
+    }
+
+    pub fn enabled(&self) -> bool { self.enabled }
+
+    // En la ventana se usan menos fotones; el próximo `update` reconstruye con la cantidad nueva.
+    pub fn set_preview(&mut self, preview: bool) { self.preview = preview; }
+
+    // Reconstruye el mapa solo si cambiaron los vóxeles o la luz se movió más de ~1°; el movimiento
+    // de las olas solo cuenta si se pidió `interval`, así que el agua animada no retraza cada cuadro.
+    pub fn update(&mut self, world: &VoxelWorld, water: &WaterMedium, to_light: Vec3, seed: u64) {
+        if !self.enabled { return; }
+        let light = [to_light.x, to_light.y, to_light.z].map(|v| (v * 64.0).round() as i32);
+        let waves = if world.waves_active() && self.interval > 0.0 { (world.wave_time() / self.interval).floor() as i64 as u64 } else { 0 };
+        let count = if self.preview { self.preview_count } else { self.photon_count };
+        let key = (world.revision(), light, waves, count, seed);
+        if self.key == Some(key) { return; }
+        self.key = Some(key);
+        self.photons.clear();
+        self.grid.clear();
+        if to_light.y <= 0.02 { return; }
+
+        // los fotones se lanzan desde un rectángulo horizontal sobre los vóxeles refractivos
+        let cells = world.refractive_voxels();
+        if cells.is_empty() { return; }
+        let (mut x0, mut x1, mut z0, mut z1, mut top) = (f32::MAX, f32::MIN, f32::MAX, f32::MIN, f32::MIN);
+        for &(x, y, z) in &cells {
+            x0 = x0.min(x as f32 - 1.0); x1 = x1.max(x as f32 + 1.0);
+            z0 = z0.min(z as f32 - 1.0); z1 = z1.max(z as f32 + 1.0);
+            top = top.max(y as f32 + 0.6);
+        }
+        let (w, d) = (x1 - x0, z1 - z0);
+        let n = count.max(1);
+        self.photon_area = w * d * to_light.y / n as f32;
+        // arranque por encima de todo el mundo, para que hojas y nubes de bloques proyecten sombra
+        let lift = (world.max_y() as f32 + 1.0 - top).max(0.0) / to_light.y;
+        let side = (n as f32).sqrt().ceil() as u32;
+        self.photons = (0..n).into_par_iter().filter_map(|i| {
+            let mut rng = Sampler::stream(seed ^ PHOTON_STREAM, i as u64, 0);
+            let u = ((i % side) as f32 + rng.next_f32()) / side as f32;
+            let v = ((i / side) as f32 + rng.next_f32()) / side as f32;
+            let start = Vec3::new(x0 + u * w, top, z0 + v * d) + to_light * lift;
+            Self::trace_photon(world, water, Ray { origin: start, dir: -to_light })
+        }).collect();
+        for (i, p) in self.photons.iter().enumerate() {
+            self.grid.entry(self.cell(p.position)).or_default().push(i as u32);
+        }
+    }
+
+    fn trace_photon(world: &VoxelWorld, water: &WaterMedium, mut ray: Ray) -> Option<Photon> {
+        let mut weight = Color::white();
+        let mut in_water = false;
+        let mut refracted = false;
+        for _ in 0..6 {
+            let hit: HitInfo = if in_water { world.intersect_from_water(&ray)? } else { world.intersect(&ray)? };
+            if in_water { weight = weight * water.transmittance(hit.t); }
+            let m = hit.material;
+            if m.kind == MaterialKind::Water {
+                let from_below = ray.dir.dot(hit.normal) > 0.0;
+                let n = if hit.normal.y > 0.5 { world.water_normal(hit.position) } else { hit.normal };
+                let (n, eta, side) = if from_below { (-n, m.ior, -hit.normal) } else { (n, 1.0 / m.ior, hit.normal) };
+                let (dir, t) = refract(ray.dir, n, eta)?;
+                weight = weight * t;
+                ray = Ray { origin: hit.position - side * (EPS * 2.0) + dir * (EPS * 4.0), dir };
+                in_water = !from_below;
+                refracted = true;
+            } else if m.transparency > 0.01 {
+                // vidrio: entra por una cara y sale por la opuesta del mismo vóxel
+                let (dir, t_in) = refract(ray.dir, hit.normal, 1.0 / m.ior)?;
+                let cell = hit.position - hit.normal * 0.5;
+                let cell = Vec3::new(cell.x.round(), cell.y.round(), cell.z.round());
+                let (t_exit, out_n) = voxel_exit(hit.position, dir, cell);
+                let exit = hit.position + dir * t_exit;
+                let (dir, t_out) = refract(dir, -out_n, m.ior)?;
+                weight = weight * m.color * (t_in * t_out * m.transparency);
+                ray = Ray { origin: exit + dir * (EPS * 4.0), dir };
+                refracted = true;
+            } else {
+                if !refracted || m.kind == MaterialKind::Cloud { return None; }
+                return Some(Photon { position: hit.position, dir: ray.dir, weight });
+            }
+        }
+        None
+    }
+
+    fn cell(&self, p: Vec3) -> (i32, i32, i32) {
+        let s = 1.0 / self.radius;
+        ((p.x * s).floor() as i32, (p.y * s).floor() as i32, (p.z * s).floor() as i32)
+    }
+
+    // Irradiancia de cáusticas en `p` (relativa a la luz principal), con un núcleo de
+    // Epanechnikov de radio `radius`. Solo cuentan los fotones que llegan por delante de `n`.
+    pub fn gather(&self, p: Vec3, n: Vec3) -> Color {
+        if self.photons.is_empty() { return Color::black(); }
+        let (cx, cy, cz) = self.cell(p);
+        let r2 = self.radius * self.radius;
+        let mut sum = Color::black();
+        for dx in -1..=1 { for dy in -1..=1 { for dz in -1..=1 {
+            let Some(ids) = self.grid.get(&(cx + dx, cy + dy, cz + dz)) else { continue; };
+            for &i in ids {
+                let ph = &self.photons[i as usize];
+                let d = ph.position - p;
+                let dist2 = d.dot(d);
+                if dist2 >= r2 || ph.dir.dot(n) >= 0.0 { continue; }
+                sum = sum + ph.weight * (1.0 - dist2 / r2);
+            }
+        }}}
+        sum * (self.photon_area * 2.0 / (std::f32::consts::PI * r2))
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashSet;
+use crate::color::{Color, Vec3};
+use crate::config::Config;
+use crate::ray_intersect::Ray;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CloudStyle {
+    Off,
+    Volumetric, // densidad de ruido 3D
+    Voxel,      // bloques como Minecraft, densidad sacada de la rejilla de vóxeles
+}
+
+// Capa de nubes entre `base` y `top`, dibujada por ray marching.
+pub struct CloudLayer {
+    pub style: CloudStyle,
+    base: f32,
+    top: f32,
+    coverage: f32,   // 0..1, fracción del cielo cubierta
+    density: f32,    // coeficiente de extinción dentro de la nube
+    scale: f32,      // tamaño de los rasgos del ruido, en vóxeles
+    wind: f32,       // vóxeles por segundo a lo largo de +x
+    steps: u32,
+    cells: HashSet<(i32, i32, i32)>,
+}
+
+pub(crate) fn hash3(x: i32, y: i32, z: i32) -> f32 {
+    let mut h = x.wrapping_mul(374761393) ^ y.wrapping_mul(668265263) ^ z.wrapping_mul(2147483647);
+    h = (h ^ (h >> 13)).wrapping_mul(1274126177);
+    ((h ^ (h >> 16)) & 0xffff) as f32 / 65535.0
+}
+
+// Ruido de valor 3D con interpolación suave.
+fn value_noise(p: Vec3) -> f32 {
+    let (ix, iy, iz) = (p.x.floor() as i32, p.y.floor() as i32, p.z.floor() as i32);
+    let s = |t: f32| t * t * (3.0 - 2.0 * t);
+    let (fx, fy, fz) = (s(p.x - ix as f32), s(p.y - iy as f32), s(p.z - iz as f32));
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let plane = |y: i32| {
+        let a = lerp(hash3(ix, y, iz), hash3(ix + 1, y, iz), fx);
+        let b = lerp(hash3(ix, y, iz + 1), hash3(ix + 1, y, iz + 1), fx);
+        lerp(a, b, fz)
+    };
+    lerp(plane(iy), plane(iy + 1), fy)
+}
+
+// Ruido fractal normalizado a [0, 1]. Devuelve 0 apenas queda claro que no va a pasar de
+// `floor`: las octavas que faltan suman a lo sumo su amplitud. Así el aire despejado, que es
+// la mayor parte de la capa, cuesta una o dos octavas en vez de todas.
+fn fbm_above(p: Vec3, octaves: u32, floor: f32) -> f32 {
+    let norm = 1.0 - 0.5_f32.powi(octaves as i32);
+    let (mut sum, mut amp, mut q, mut left) = (0.0, 0.5, p, norm);
+    for _ in 0..octaves {
+        sum += value_noise(q) * amp;
+        left -= amp;
+        if (sum + left) / norm <= floor { return 0.0; }
+        amp *= 0.5;
+        q = q * 2.03;
+    }
+    sum / norm
+}
+
+// Henyey-Greenstein multiplicada por 4π, para que la dispersión isotrópica valga 1.
+pub fn henyey_greenstein(cos_theta: f32, g: f32) -> f32 {
+    let denom = (1.0 + g * g - 2.0 * g * cos_theta).max(1e-4);
+    (1.0 - g * g) / (denom * denom.sqrt())
+}
+
+impl CloudLayer {
+    // `cells` son los vóxeles de nube que armó la isla; su altura da la altitud por defecto.
+    pub fn from_config(cfg: &Config, cells: Vec<(i32, i32, i32)>) -> Self {
+        let style = match cfg.get_str("clouds").unwrap_or("volumetric") {
+            "off" | "none" | "false" => CloudStyle::Off,
+            "voxel" | "blocky" => CloudStyle::Voxel,
+            "volumetric" => CloudStyle::Volumetric,
+            other => { eprintln!("[clouds] unknown style '{}', using volumetric", other); CloudStyle::Volumetric }
+        };
+        let voxel_y = cells.iter().map(|c| c.1).min().unwrap_or(30);
+        let altitude = cfg.get_f32("cloud_altitude", voxel_y as f32);
+        // los bloques se mueven a la altitud pedida, conservando su forma
+        let shift = altitude.round() as i32 - voxel_y;
+        let cells: HashSet<(i32, i32, i32)> = cells.into_iter().map(|(x, y, z)| (x, y + shift, z)).collect();
+        let (base, top) = match style {
+            CloudStyle::Voxel => {
+                let max_y = cells.iter().map(|c| c.1).max().unwrap_or(altitude as i32);
+                (altitude.round() - 0.5, max_y as f32 + 0.5)
+            }
+            _ => (altitude - 0.5, altitude - 0.5 + cfg.get_f32("cloud_thickness", 6.0)),
+        };
+        Self {
+            style, base, top, cells,
+            coverage: cfg.get_f32("cloud_coverage", 0.45).clamp(0.0, 1.0),
+            density: cfg.get_f32("cloud_density", 0.8),
+            scale: cfg.get_f32("cloud_scale", 14.0).max(0.1),
+            wind: cfg.get_f32("cloud_wind", 0.4),
+            steps: cfg.get_u32("cloud_steps", 32).max(4),
+        }
+    }
+
+    fn density_at(&self, p: Vec3, time: f32, octaves: u32) -> f32 {
+        match self.style {
+            CloudStyle::Off => 0.0,
+            CloudStyle::Voxel => {
+                let c = ((p.x + 0.5).floor() as i32, (p.y + 0.5).floor() as i32, (p.z + 0.5).floor() as i32);
+                if self.cells.contains(&c) { self.density * 2.0 } else { 0.0 }
+            }
+            CloudStyle::Volumetric => {
+                let h = ((p.y - self.base) / (self.top - self.base)).clamp(0.0, 1.0);
+                // base plana y cima redondeada
+                let profile = (h * 4.0).min(1.0) * (1.0 - h).powf(0.7);
+                let threshold = (1.0 - self.coverage) * 0.75;
+                if profile <= threshold { return 0.0; }
+                let q = Vec3::new(p.x - time * self.wind, p.y, p.z) * (1.0 / self.scale);
+                let n = fbm_above(q, octaves, threshold / profile) * profile;
+                ((n - threshold) * 4.0).clamp(0.0, 1.0) * self.density
+            }
+        }
+    }
+
+    // Tramo [t0, t1] del rayo dentro de la capa, si lo hay.
+    fn slab(&self, origin: Vec3, dir: Vec3, t_max: f32) -> Option<(f32, f32)> {
+        if self.style == CloudStyle::Off { return None; }
+        let (t0, t1) = if dir.y.abs() < 1e-5 {
+            if origin.y < self.base || origin.y > self.top { return None; }
+            (0.0, f32::INFINITY)
+        } else {
+            let a = (self.base - origin.y) / dir.y;
+            let b = (self.top - origin.y) / dir.y;
+            (a.min(b).max(0.0), a.max(b))
+        };
+        // los rayos casi horizontales recorren la capa por kilómetros; se corta a una distancia razonable
+        let t1 = t1.min(t_max).min(t0 + 120.0);
+        if t1 <= t0 { None } else { Some((t0, t1)) }
+    }
+
+    // Fracción de luz que atraviesa la capa desde `p` hacia la luz (sombras de nubes).
+    pub fn transmittance(&self, p: Vec3, to_light: Vec3, time: f32) -> f32 {
+        let Some((t0, t1)) = self.slab(p, to_light, f32::INFINITY) else { return 1.0; };
+        const STEPS: u32 = 8;
+        let dt = (t1 - t0) / STEPS as f32;
+        let mut tau = 0.0;
+        for i in 0..STEPS {
+            tau += self.density_at(p + to_light * (t0 + (i as f32 + 0.5) * dt), time, 3) * dt;
+        }
+        (-tau).exp()
+    }
+
+    // Compone las nubes sobre `background` (lo que el rayo ve a distancia `t_max`).
+    // `light` es la luz directa (dirección hacia ella y color) y `ambient` la luz del cielo.
+    // Devuelve además la fracción de `background` que atraviesa las nubes.
+    pub fn composite(&self, ray: &Ray, t_max: f32, background: Color, light: (Vec3, Color), ambient: Color, time: f32) -> (Color, f32) {
+        let (origin, dir) = (ray.origin, ray.dir);
+        let Some((t0, t1)) = self.slab(origin, dir, t_max) else { return (background, 1.0); };
+        let (to_light, light_col) = light;
+        let cos_theta = dir.dot(to_light);
+        // lóbulo hacia adelante (borde plateado) más uno débil hacia atrás
+        let phase = henyey_greenstein(cos_theta, 0.6) * 0.7 + henyey_greenstein(cos_theta, -0.25) * 0.3;
+        let dt = (t1 - t0) / self.steps as f32;
+        // desfase por píxel para no ver capas en el marching
+        let jitter = hash3((dir.x * 4096.0) as i32, (dir.y * 4096.0) as i32, (dir.z * 4096.0) as i32);
+        let mut transmittance = 1.0;
+        let mut scattered = Color::black();
+        for i in 0..self.steps {
+            let t = t0 + (i as f32 + jitter) * dt;
+            let p = origin + dir * t;
+            let sigma = self.density_at(p, time, 4);
+            if sigma <= 0.0 { continue; }
+            let light_t = self.transmittance(p, to_light, time);
+            // la dispersión múltiple se aproxima con un segundo término que se atenúa mucho menos
+            let light_t = light_t + 0.5 * light_t.powf(0.25);
+            // "powder": los bordes delgados se ven más oscuros que el interior iluminado
+            let powder = 1.0 - (-2.0 * sigma * dt).exp();
+            let h = ((p.y - self.base) / (self.top - self.base)).clamp(0.0, 1.0);
+            let in_light = light_col * (light_t * phase * (0.5 + 0.5 * powder)) + ambient * (2.0 + 1.5 * h);
+            let step_t = (-sigma * dt).exp();
+            scattered = scattered + in_light * (transmittance * (1.0 - step_t));
+            transmittance *= step_t;
+            if transmittance < 0.01 { break; }
+        }
+        (background * transmittance + scattered, transmittance)
+    }
+}
@@ -20,7 +20,21 @@ impl Color {
             self.b.clamp(0.0, 1.0),
         )
     }
-    pub fn to_rgb8(self) -> [u8;3] { [(self.r * 255.0) as u8, (self.g * 255.0) as u8, (self.b * 255.0) as u8] }
+    // Para valores ya listos para pantalla (mapeados y codificados en sRGB): redondea a 8 bits.
+    pub fn to_rgb8(self) -> [u8;3] {
+        let c = self.clamped();
+        [(c.r * 255.0 + 0.5) as u8, (c.g * 255.0 + 0.5) as u8, (c.b * 255.0 + 0.5) as u8]
+    }
+    pub fn luminance(self) -> f32 { 0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b }
+    // Curva sRGB exacta (tramo lineal cerca del negro, potencia 2.4 en el resto).
+    pub fn srgb_to_linear(self) -> Self {
+        let f = |c: f32| if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) };
+        Self::new(f(self.r), f(self.g), f(self.b))
+    }
+    pub fn linear_to_srgb(self) -> Self {
+        let f = |c: f32| { let c = c.clamp(0.0, 1.0); if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 } };
+        Self::new(f(self.r), f(self.g), f(self.b))
+    }
 }
 
 impl Add for Color {
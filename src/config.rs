@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use crate::color::Vec3;
+
+// Opciones de render. Se leen de la línea de comandos (`--clave valor`, `--clave=valor`
+// o `--bandera`) y, si se pasa `--config archivo`, de un archivo con líneas `clave = valor`.
+// Los guiones de las claves se normalizan a `_`; la línea de comandos gana sobre el archivo.
+pub struct Config {
+    values: HashMap<String, String>,
+}
+
+impl Config {
+    pub fn from_args() -> Self { Self::parse(std::env::args().skip(1).collect()) }
+
+    pub fn parse(args: Vec<String>) -> Self {
+        let mut cli = HashMap::new();
+        let mut i = 0;
+        while i < args.len() {
+            if let Some(key) = args[i].strip_prefix("--") {
+                if let Some((k, v)) = key.split_once('=') {
+                    cli.insert(k.replace('-', "_"), v.to_string());
+                } else if i + 1 < args.len() && !args[i + 1].starts_with("--") {
+                    cli.insert(key.replace('-', "_"), args[i + 1].clone());
+                    i += 1;
+                } else {
+                    cli.insert(key.replace('-', "_"), "true".to_string());
+                }
+            } else {
+                eprintln!("[config] ignoring argument '{}'", args[i]);
+            }
+            i += 1;
+        }
+        let mut values = HashMap::new();
+        if let Some(path) = cli.get("config") {
+            match std::fs::read_to_string(path) {
+                Ok(text) => {
+                    for line in text.lines() {
+                        let line = line.split('#').next().unwrap_or("").trim();
+                        if let Some((k, v)) = line.split_once('=') {
+                            values.insert(k.trim().replace('-', "_"), v.trim().to_string());
+                        }
+                    }
+                }
+                Err(err) => eprintln!("[config] error reading {}: {}", path, err),
+            }
+        }
+        values.extend(cli);
+        Self { values }
+    }
+
+    // Todas las opciones ya resueltas, para reenviarlas (render distribuido) y rearmarlas del otro lado.
+    pub fn entries(&self) -> Vec<(String, String)> { self.values.iter().map(|(k, v)| (k.clone(), v.clone())).collect() }
+    pub fn from_entries(entries: Vec<(String, String)>) -> Self { Self { values: entries.into_iter().collect() } }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> { self.values.get(key).map(|s| s.as_str()) }
+
+    fn get_parsed<T: std::str::FromStr>(&self, key: &str, default: T) -> T {
+        match self.values.get(key) {
+            None => default,
+            Some(v) => v.parse().unwrap_or_else(|_| {
+                eprintln!("[config] invalid value for {}: '{}'", key, v);
+                default
+            }),
+        }
+    }
+    pub fn get_f32(&self, key: &str, default: f32) -> f32 { self.get_parsed(key, default) }
+    pub fn get_u32(&self, key: &str, default: u32) -> u32 { self.get_parsed(key, default) }
+    pub fn get_u64(&self, key: &str, default: u64) -> u64 { self.get_parsed(key, default) }
+    // Tres números separados por comas, como `--position 0,12,-3`.
+    pub fn get_vec3(&self, key: &str) -> Option<Vec3> {
+        let v = self.values.get(key)?;
+        let parts: Vec<f32> = v.split(',').filter_map(|p| p.trim().parse().ok()).collect();
+        if parts.len() != 3 {
+            eprintln!("[config] invalid value for {}: '{}' (expected x,y,z)", key, v);
+            return None;
+        }
+        Some(Vec3::new(parts[0], parts[1], parts[2]))
+    }
+    pub fn get_bool(&self, key: &str, default: bool) -> bool {
+        match self.get_str(key) {
+            None => default,
+            Some("true") | Some("1") | Some("on") | Some("yes") => true,
+            Some("false") | Some("0") | Some("off") | Some("no") => false,
+            Some(v) => { eprintln!("[config] invalid value for {}: '{}'", key, v); default }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> { list.iter().map(|a| a.to_string()).collect() }
+
+    #[test]
+    fn parses_values_equals_flags_and_dashes() {
+        let cfg = Config::parse(args(&["--fov", "45", "--env-rotation=90", "--time-paused", "--snow", "yes", "stray", "--offset", "-3"]));
+        assert_eq!(cfg.get_f32("fov", 60.0), 45.0);
+        assert_eq!(cfg.get_f32("env_rotation", 0.0), 90.0);
+        assert!(cfg.get_bool("time_paused", false));
+        assert!(cfg.get_bool("snow", false));
+        assert_eq!(cfg.get_f32("offset", 0.0), -3.0);
+        assert_eq!(cfg.get_str("stray"), None);
+        assert_eq!(cfg.get_str("missing"), None);
+    }
+
+    #[test]
+    fn bad_or_missing_values_fall_back_to_the_default() {
+        let cfg = Config::parse(args(&["--fov", "wide", "--env-samples", "-2", "--snow", "maybe"]));
+        assert_eq!(cfg.get_f32("fov", 60.0), 60.0);
+        assert_eq!(cfg.get_u32("env_samples", 4), 4);
+        assert!(cfg.get_bool("snow", true));
+        assert!(!cfg.get_bool("fog", false));
+        assert_eq!(cfg.get_u32("height", 600), 600);
+    }
+
+    #[test]
+    fn command_line_wins_over_the_config_file() {
+        let path = std::env::temp_dir().join(format!("proyecto2-config-{}.cfg", std::process::id()));
+        std::fs::write(&path, "# escena\nfov = 30\nenv-intensity = 2 # más brillo\nsin igual\n").unwrap();
+        let cfg = Config::parse(args(&["--config", path.to_str().unwrap(), "--fov", "50"]));
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(cfg.get_f32("fov", 60.0), 50.0);
+        assert_eq!(cfg.get_f32("env_intensity", 1.0), 2.0);
+        assert_eq!(cfg.get_str("sin igual"), None);
+    }
+}
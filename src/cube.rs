@@ -1,7 +1,7 @@
 
 use crate::color::Vec3;
 use crate::material::Material;
-use crate::ray_intersect::{HitInfo, Ray, SceneObject, ObjectId};
+use crate::ray_intersect::{HitInfo, Ray, SceneObject, ObjectId, face_tangents};
 
 pub struct Cube {
     pub center: Vec3,
@@ -79,6 +79,7 @@ impl SceneObject for Cube {
         }
 
     let (u, v) = self.face_uv(position, normal);
-    Some(HitInfo { t: t_hit, position, normal, material: self.material, object_id: ObjectId::Cube, u, v })
+    let (tangent, bitangent) = face_tangents(normal);
+    Some(HitInfo { t: t_hit, position, normal, material: self.material, object_id: ObjectId::Cube, u, v, tangent, bitangent })
     }
 }
@@ -0,0 +1,117 @@
+use crate::color::{Color, Vec3};
+use crate::config::Config;
+use std::f32::consts::TAU;
+
+const AXIAL_TILT: f32 = 23.44;       // grados
+const SYNODIC_MONTH: f32 = 29.53;    // días entre lunas nuevas
+
+// Reloj del día: hora solar local, fecha y latitud determinan dónde están el sol y la luna.
+// Mundo: +x = este, +y = arriba, -z = norte.
+pub struct DayCycle {
+    pub hours: f32,       // 0..24
+    pub day_of_year: f32, // 1..365
+    pub latitude: f32,    // grados, positivo al norte
+    pub speed: f32,       // horas simuladas por segundo real
+    pub paused: bool,
+}
+
+// "HH:MM" o un número de horas.
+pub fn parse_hours(s: &str) -> Option<f32> {
+    match s.split_once(':') {
+        Some((h, m)) => Some(h.trim().parse::<f32>().ok()? + m.trim().parse::<f32>().ok()? / 60.0),
+        None => s.trim().parse().ok(),
+    }
+}
+
+// "MM-DD" o el número de día del año.
+fn parse_date(s: &str) -> Option<f32> {
+    const MONTH_START: [u32; 12] = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
+    match s.split_once('-') {
+        Some((m, d)) => {
+            let m: usize = m.trim().parse().ok()?;
+            let d: u32 = d.trim().parse().ok()?;
+            if !(1..=12).contains(&m) || !(1..=31).contains(&d) { return None; }
+            Some((MONTH_START[m - 1] + d) as f32)
+        }
+        None => s.trim().parse().ok(),
+    }
+}
+
+// Dirección en el cielo para una declinación y ángulo horario dados (radianes).
+fn sky_direction(declination: f32, hour_angle: f32, latitude: f32) -> Vec3 {
+    let east = -declination.cos() * hour_angle.sin();
+    let north = declination.sin() * latitude.cos() - declination.cos() * hour_angle.cos() * latitude.sin();
+    let up = declination.sin() * latitude.sin() + declination.cos() * hour_angle.cos() * latitude.cos();
+    Vec3::new(east, up, -north).normalized()
+}
+
+// Aproximación de Tanner Helland del color de un cuerpo negro (1000K..40000K).
+pub fn kelvin_to_rgb(kelvin: f32) -> Color {
+    let t = kelvin.clamp(1000.0, 40000.0) / 100.0;
+    let r = if t <= 66.0 { 255.0 } else { 329.699 * (t - 60.0).powf(-0.1332) };
+    let g = if t <= 66.0 { 99.4708 * t.ln() - 161.1196 } else { 288.122 * (t - 60.0).powf(-0.0755) };
+    let b = if t >= 66.0 { 255.0 } else if t <= 19.0 { 0.0 } else { 138.5177 * (t - 10.0).ln() - 305.0448 };
+    Color::new(r.clamp(0.0, 255.0) / 255.0, g.clamp(0.0, 255.0) / 255.0, b.clamp(0.0, 255.0) / 255.0)
+}
+
+impl DayCycle {
+    pub fn from_config(cfg: &Config, default_hours: f32) -> Self {
+        let hours = cfg.get_str("time").and_then(parse_hours).unwrap_or(default_hours);
+        let day_of_year = cfg.get_str("date").and_then(parse_date).unwrap_or(172.0);
+        Self {
+            hours: hours.rem_euclid(24.0),
+            day_of_year,
+            latitude: cfg.get_f32("latitude", 14.6),
+            speed: cfg.get_f32("time_speed", 0.1),
+            paused: cfg.get_bool("time_paused", false),
+        }
+    }
+
+    pub fn advance(&mut self, dt: f32) {
+        if !self.paused { self.scrub(dt * self.speed); }
+    }
+
+    // Mueve el reloj `hours` horas (negativo hacia atrás), pasando de un día al siguiente.
+    pub fn scrub(&mut self, hours: f32) {
+        let total = self.hours + hours;
+        self.day_of_year = (self.day_of_year - 1.0 + (total / 24.0).floor()).rem_euclid(365.0) + 1.0;
+        self.hours = total.rem_euclid(24.0);
+    }
+
+    fn days(&self) -> f32 { self.day_of_year + self.hours / 24.0 }
+    fn hour_angle(&self) -> f32 { (self.hours - 12.0) * 15.0_f32.to_radians() }
+    fn solar_declination(&self) -> f32 { -AXIAL_TILT.to_radians() * (TAU / 365.0 * (self.days() + 10.0)).cos() }
+
+    pub fn sun_direction(&self) -> Vec3 {
+        sky_direction(self.solar_declination(), self.hour_angle(), self.latitude.to_radians())
+    }
+
+    // Elongación de la luna respecto del sol (0 = luna nueva, π = luna llena).
+    fn moon_elongation(&self) -> f32 { (self.days() / SYNODIC_MONTH).fract() * TAU }
+
+    pub fn moon_direction(&self) -> Vec3 {
+        let e = self.moon_elongation();
+        // la luna sigue la eclíptica, desplazada `e` respecto del sol
+        let declination = -AXIAL_TILT.to_radians() * (TAU / 365.0 * (self.days() + 10.0) + e).cos();
+        sky_direction(declination, self.hour_angle() - e, self.latitude.to_radians())
+    }
+
+    // Fracción iluminada del disco lunar.
+    pub fn moon_illumination(&self) -> f32 { 0.5 * (1.0 - self.moon_elongation().cos()) }
+
+    // Tiempo sidéreo local en radianes: ángulo horario del punto vernal. Las estrellas
+    // (ascensión recta α) están en el ángulo horario `sidereal_angle - α`.
+    pub fn sidereal_angle(&self) -> f32 {
+        (self.hour_angle() + TAU * (self.days() - 80.0) / 365.25).rem_euclid(TAU)
+    }
+
+    // Color de la luz de luna según la fase (luz solar reflejada, algo más fría a la vista).
+    pub fn moon_light(&self) -> Color {
+        kelvin_to_rgb(7500.0) * (0.12 * self.moon_illumination())
+    }
+
+    pub fn clock_string(&self) -> String {
+        let minutes = (self.hours * 60.0) as u32;
+        format!("{:02}:{:02} day {}", minutes / 60, minutes % 60, self.day_of_year.floor() as u32)
+    }
+}
@@ -0,0 +1,97 @@
+use rayon::prelude::*;
+use crate::aov::{AovSample, MISS_DEPTH};
+use crate::color::Color;
+use crate::config::Config;
+
+// Filtro à-trous con bordes (Dammertz et al. 2010, con la varianza de SVGF): varias pasadas de
+// un núcleo B3 de 5×5 con huecos que se duplican, frenado por diferencias de normal, profundidad
+// y brillo. La tolerancia de brillo sale de la varianza local, así se alisa el ruido pero no los
+// bordes de sombra. Se filtra la iluminación (color / albedo) para no borrar las texturas.
+pub struct Denoiser {
+    pub enabled: bool,
+    pub strength: f32,   // escala la tolerancia de brillo; 0 no filtra, 2 alisa mucho
+    iterations: u32,
+    sigma_normal: f32,   // exponente sobre el coseno entre normales
+    sigma_depth: f32,    // diferencia de profundidad relativa tolerada
+}
+
+const KERNEL: [f32; 3] = [3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+impl Denoiser {
+    pub fn from_config(cfg: &Config) -> Self {
+        Self {
+            enabled: cfg.get_bool("denoise", false),
+            strength: cfg.get_f32("denoise_strength", 1.0).max(0.0),
+            iterations: cfg.get_u32("denoise_iterations", 4).clamp(1, 8),
+            sigma_normal: cfg.get_f32("denoise_normal", 64.0).max(1.0),
+            sigma_depth: cfg.get_f32("denoise_depth", 0.05).max(1e-4),
+        }
+    }
+
+    pub fn apply(&self, pixels: &[Color], aovs: &[AovSample], width: u32, height: u32) -> Vec<Color> {
+        let n = (width * height) as usize;
+        if !self.enabled || self.strength <= 0.0 || pixels.len() != n || aovs.len() != n { return pixels.to_vec(); }
+        let (w, h) = (width as i32, height as i32);
+        // piso al albedo para no dividir por cero en texturas negras
+        let albedo = |s: &AovSample| Color::new(s.albedo.r.max(1e-3), s.albedo.g.max(1e-3), s.albedo.b.max(1e-3));
+        let mut light: Vec<Color> = pixels.par_iter().zip(aovs).map(|(&c, s)| {
+            if s.depth >= MISS_DEPTH { c } else { let a = albedo(s); Color::new(c.r / a.r, c.g / a.g, c.b / a.b) }
+        }).collect();
+        // varianza del brillo en un entorno de 3×3 de la misma superficie
+        let mut variance: Vec<f32> = (0..n).into_par_iter().map(|i| {
+            let (x, y) = (i as i32 % w, i as i32 / w);
+            let (mut m1, mut m2, mut count) = (0.0, 0.0, 0.0);
+            for dy in -1i32..=1 {
+                for dx in -1i32..=1 {
+                    let (sx, sy) = (x + dx, y + dy);
+                    if sx < 0 || sy < 0 || sx >= w || sy >= h { continue; }
+                    let j = (sy * w + sx) as usize;
+                    if aovs[j].depth >= MISS_DEPTH || aovs[i].normal.dot(aovs[j].normal) < 0.9 { continue; }
+                    let l = light[j].luminance();
+                    m1 += l; m2 += l * l; count += 1.0;
+                }
+            }
+            if count > 0.0 { (m2 / count - (m1 / count) * (m1 / count)).max(0.0) } else { 0.0 }
+        }).collect();
+        for it in 0..self.iterations {
+            let step = 1i32 << it;
+            let (next, next_var): (Vec<Color>, Vec<f32>) = (0..n).into_par_iter().map(|i| {
+                let center = &aovs[i];
+                let c = light[i];
+                // el cielo no tiene ruido ni geometría que respetar
+                if center.depth >= MISS_DEPTH { return (c, variance[i]); }
+                let (x, y) = (i as i32 % w, i as i32 / w);
+                let lc = c.luminance();
+                let sigma_l = 4.0 * self.strength * variance[i].sqrt() + 1e-4;
+                let mut sum = Color::black();
+                let mut var_sum = 0.0;
+                let mut total = 0.0;
+                for dy in -2i32..=2 {
+                    for dx in -2i32..=2 {
+                        let (sx, sy) = (x + dx * step, y + dy * step);
+                        if sx < 0 || sy < 0 || sx >= w || sy >= h { continue; }
+                        let j = (sy * w + sx) as usize;
+                        let s = &aovs[j];
+                        if s.depth >= MISS_DEPTH { continue; }
+                        let k = KERNEL[dx.unsigned_abs() as usize] * KERNEL[dy.unsigned_abs() as usize];
+                        let wn = center.normal.dot(s.normal).max(0.0).powf(self.sigma_normal);
+                        let wz = (-(center.depth - s.depth).abs() / (self.sigma_depth * center.depth * step as f32)).exp();
+                        let lj = light[j].luminance();
+                        let wl = (-(lc - lj).abs() / sigma_l).exp();
+                        let weight = k * wn * wz * wl;
+                        sum = sum + light[j] * weight;
+                        var_sum += weight * weight * variance[j];
+                        total += weight;
+                    }
+                }
+                // la varianza baja con cada pasada, y con ella la tolerancia
+                if total > 0.0 { (sum * (1.0 / total), var_sum / (total * total)) } else { (c, variance[i]) }
+            }).unzip();
+            light = next;
+            variance = next_var;
+        }
+        light.par_iter().zip(aovs).map(|(&l, s)| {
+            if s.depth >= MISS_DEPTH { l } else { let a = albedo(s); Color::new(l.r * a.r, l.g * a.g, l.b * a.b) }
+        }).collect()
+    }
+}
@@ -0,0 +1,401 @@
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::color::{Color, Vec3};
+use crate::config::Config;
+use crate::tiles::Progress;
+
+// Render distribuido entre máquinas por TCP. El coordinador manda una vez la escena (las opciones
+// con las que se arma el mundo y sus materiales, las texturas y el mapa de entorno) y después va
+// repartiendo trabajos: un bloque de la imagen o un cuadro entero con su cámara. Cada trabajador
+// arma la escena igual que el coordinador, lo comprueba con la huella del mundo y devuelve la
+// radiancia del bloque. Si un trabajador se cae o tarda demasiado, su trabajo vuelve a la cola.
+//
+// Mensajes: un byte de tipo, el largo (u32) y el contenido, todo en little-endian.
+const SCENE: u8 = 1;
+const JOB: u8 = 2;
+const RESULT: u8 = 3;
+const DONE: u8 = 4;
+
+// Tope para no reservar cualquier cosa si llega basura por el socket.
+const MAX_MESSAGE: usize = 1 << 30;
+
+// Un bloque de `rect` (x, y, ancho, alto) dentro de una imagen de `width`×`height`, trazado con
+// la cámara, el reloj, la hora del día y la semilla que tenía el coordinador.
+#[derive(Clone)]
+pub struct Job {
+    pub id: u32,
+    pub width: u32,
+    pub height: u32,
+    pub rect: (u32, u32, u32, u32),
+    pub time: f32,
+    pub hours: f32,
+    pub seed: u64,
+    pub fov: f32,
+    pub focus_distance: f32,
+    pub position: Vec3,
+    pub basis: (Vec3, Vec3, Vec3),
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u32(&mut self, v: u32) { self.0.extend_from_slice(&v.to_le_bytes()); }
+    fn u64(&mut self, v: u64) { self.0.extend_from_slice(&v.to_le_bytes()); }
+    fn f32(&mut self, v: f32) { self.0.extend_from_slice(&v.to_le_bytes()); }
+    fn vec3(&mut self, v: Vec3) { self.f32(v.x); self.f32(v.y); self.f32(v.z); }
+    fn bytes(&mut self, b: &[u8]) { self.u32(b.len() as u32); self.0.extend_from_slice(b); }
+    fn str(&mut self, s: &str) { self.bytes(s.as_bytes()); }
+}
+
+struct Reader<'a> { buf: &'a [u8], pos: usize }
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(n).filter(|&e| e <= self.buf.len()).ok_or("truncated message")?;
+        let out = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(out)
+    }
+    fn u32(&mut self) -> Result<u32, String> { Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap())) }
+    fn u64(&mut self) -> Result<u64, String> { Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap())) }
+    fn f32(&mut self) -> Result<f32, String> { Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap())) }
+    fn vec3(&mut self) -> Result<Vec3, String> { Ok(Vec3::new(self.f32()?, self.f32()?, self.f32()?)) }
+    fn bytes(&mut self) -> Result<&'a [u8], String> { let n = self.u32()? as usize; self.take(n) }
+    fn str(&mut self) -> Result<String, String> { String::from_utf8(self.bytes()?.to_vec()).map_err(|e| e.to_string()) }
+}
+
+fn send(stream: &mut TcpStream, kind: u8, payload: &[u8]) -> Result<(), String> {
+    let mut head = [kind, 0, 0, 0, 0];
+    head[1..].copy_from_slice(&(payload.len() as u32).to_le_bytes());
+    stream.write_all(&head).and_then(|_| stream.write_all(payload)).map_err(|e| e.to_string())
+}
+
+fn receive(stream: &mut TcpStream) -> Result<(u8, Vec<u8>), String> {
+    let mut head = [0u8; 5];
+    stream.read_exact(&mut head).map_err(|e| e.to_string())?;
+    let len = u32::from_le_bytes(head[1..].try_into().unwrap()) as usize;
+    if len > MAX_MESSAGE { return Err(format!("message too large ({} bytes)", len)); }
+    let mut payload = vec![0u8; len];
+    stream.read_exact(&mut payload).map_err(|e| e.to_string())?;
+    Ok((head[0], payload))
+}
+
+impl Job {
+    fn encode(&self) -> Vec<u8> {
+        let mut w = Writer(Vec::new());
+        for v in [self.id, self.width, self.height, self.rect.0, self.rect.1, self.rect.2, self.rect.3] { w.u32(v); }
+        for v in [self.time, self.hours] { w.f32(v); }
+        w.u64(self.seed);
+        for v in [self.fov, self.focus_distance] { w.f32(v); }
+        for v in [self.position, self.basis.0, self.basis.1, self.basis.2] { w.vec3(v); }
+        w.0
+    }
+
+    fn decode(buf: &[u8]) -> Result<Self, String> {
+        let mut r = Reader { buf, pos: 0 };
+        let (id, width, height) = (r.u32()?, r.u32()?, r.u32()?);
+        let rect = (r.u32()?, r.u32()?, r.u32()?, r.u32()?);
+        // todo con sumas y productos comprobados: un trabajo mal formado es un error, no un pánico
+        let inside = |start: u32, len: u32, size: u32| len > 0 && start.checked_add(len).is_some_and(|end| end <= size);
+        if width.checked_mul(height).is_none() || !inside(rect.0, rect.2, width) || !inside(rect.1, rect.3, height) {
+            return Err(format!("job {}: bad rect {:?} in {}x{}", id, rect, width, height));
+        }
+        Ok(Self {
+            id, width, height, rect,
+            time: r.f32()?, hours: r.f32()?, seed: r.u64()?, fov: r.f32()?, focus_distance: r.f32()?,
+            position: r.vec3()?,
+            basis: (r.vec3()?, r.vec3()?, r.vec3()?),
+        })
+    }
+
+    // Índices (en la imagen completa) de los píxeles del bloque, fila por fila. `decode` ya
+    // comprobó que el bloque cabe en la imagen, así que ningún índice pasa de `width * height`.
+    pub fn pixels(&self) -> impl Iterator<Item = usize> + '_ {
+        let (x, y, w, h) = (self.rect.0 as usize, self.rect.1 as usize, self.rect.2 as usize, self.rect.3 as usize);
+        let width = self.width as usize;
+        (y..y + h).flat_map(move |py| (x..x + w).map(move |px| py * width + px))
+    }
+}
+
+// Resultado de un trabajo: su id y la radiancia de cada píxel del bloque.
+fn encode_result(id: u32, pixels: &[Color]) -> Vec<u8> {
+    let mut w = Writer(Vec::with_capacity(4 + pixels.len() * 12));
+    w.u32(id);
+    for c in pixels { w.f32(c.r); w.f32(c.g); w.f32(c.b); }
+    w.0
+}
+
+fn decode_result(payload: &[u8], job: &Job) -> Result<Vec<Color>, String> {
+    let mut r = Reader { buf: payload, pos: 0 };
+    let id = r.u32()?;
+    let n = job.rect.2 as usize * job.rect.3 as usize;
+    if id != job.id || payload.len() != 4 + n * 12 { return Err(format!("bad result for job {}", job.id)); }
+    (0..n).map(|_| Ok(Color::new(r.f32()?, r.f32()?, r.f32()?))).collect()
+}
+
+// Archivos de `dir` (con subcarpetas), con la ruta relativa al directorio actual.
+fn collect_files(dir: &Path, out: &mut Vec<(String, Vec<u8>)>) -> Result<(), String> {
+    let entries = std::fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(&path, out)?;
+        } else {
+            let data = std::fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            out.push((path.to_string_lossy().replace('\\', "/"), data));
+        }
+    }
+    Ok(())
+}
+
+// La escena tal como viaja a los trabajadores: opciones, texturas, mapa de entorno y la huella del
+// mundo que deberían obtener al armarlo.
+fn bundle_scene(cfg: &Config, fingerprint: u64) -> Result<Vec<u8>, String> {
+    let mut files = Vec::new();
+    if Path::new("textures").is_dir() { collect_files(Path::new("textures"), &mut files)?; }
+    // lo que decide qué hace el proceso (y no cómo se ve la escena) se queda aquí
+    let mut entries = cfg.entries();
+    entries.retain(|(k, _)| !matches!(k.as_str(), "coordinator" | "worker" | "output" | "animation" | "config" | "time_limit"));
+    // el mapa de entorno puede estar en cualquier lado: viaja con un nombre fijo
+    if let Some(path) = cfg.get_str("env") {
+        let name = Path::new(path).file_name().map_or("env".into(), |n| n.to_string_lossy().into_owned());
+        let data = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        files.push((format!("env/{}", name), data));
+        for (k, v) in entries.iter_mut() { if k == "env" { *v = format!("env/{}", name); } }
+    }
+    let mut w = Writer(Vec::new());
+    w.u64(fingerprint);
+    w.u32(entries.len() as u32);
+    for (k, v) in &entries { w.str(k); w.str(v); }
+    w.u32(files.len() as u32);
+    for (path, data) in &files { w.str(path); w.bytes(data); }
+    eprintln!("[coordinator] scene bundle: {} options, {} files, {} KiB", entries.len(), files.len(), w.0.len() / 1024);
+    Ok(w.0)
+}
+
+pub struct Worker { stream: TcpStream, fingerprint: u64, dir: PathBuf }
+
+impl Worker {
+    // Se conecta (reintentando un rato, por si el coordinador todavía no arrancó), recibe la escena,
+    // deja sus archivos en una carpeta temporal que pasa a ser el directorio actual y devuelve las
+    // opciones con las que hay que armarla.
+    pub fn connect(addr: &str) -> Result<(Self, Config), String> {
+        let started = Instant::now();
+        let mut stream = loop {
+            match TcpStream::connect(addr) {
+                Ok(stream) => break stream,
+                Err(err) if started.elapsed() < Duration::from_secs(30) => {
+                    eprintln!("[worker] {}: {}, retrying", addr, err);
+                    std::thread::sleep(Duration::from_secs(1));
+                }
+                Err(err) => return Err(format!("{}: {}", addr, err)),
+            }
+        };
+        let _ = stream.set_nodelay(true);
+        let (kind, payload) = receive(&mut stream)?;
+        if kind != SCENE { return Err(format!("expected scene, got message {}", kind)); }
+        let mut r = Reader { buf: &payload, pos: 0 };
+        let fingerprint = r.u64()?;
+        let entries = (0..r.u32()?).map(|_| Ok((r.str()?, r.str()?))).collect::<Result<Vec<_>, String>>()?;
+        let dir: PathBuf = std::env::temp_dir().join(format!("proyecto2-worker-{}", std::process::id()));
+        for _ in 0..r.u32()? {
+            let (path, data) = (r.str()?, r.bytes()?);
+            // solo rutas relativas hacia adentro de la carpeta
+            if Path::new(&path).is_absolute() || path.split('/').any(|c| c == "..") { return Err(format!("refusing path '{}'", path)); }
+            let target = dir.join(&path);
+            if let Some(parent) = target.parent() { std::fs::create_dir_all(parent).map_err(|e| e.to_string())?; }
+            std::fs::write(&target, data).map_err(|e| format!("{}: {}", target.display(), e))?;
+        }
+        std::fs::create_dir_all(&dir).and_then(|_| std::env::set_current_dir(&dir)).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        eprintln!("[worker] connected to {}, scene in {}", addr, dir.display());
+        Ok((Self { stream, fingerprint, dir }, Config::from_entries(entries)))
+    }
+
+    // Atiende trabajos hasta que el coordinador avisa que terminó. Si la conexión se corta antes,
+    // es un error. `world_fingerprint` es la huella del mundo que se armó aquí; si no coincide, no se traza nada.
+    pub fn serve(&mut self, world_fingerprint: u64, mut render: impl FnMut(&Job) -> Vec<Color>) -> Result<(), String> {
+        if world_fingerprint != self.fingerprint {
+            return Err(format!("world mismatch (coordinator {:016x}, here {:016x}); are both builds the same?", self.fingerprint, world_fingerprint));
+        }
+        let mut done = 0;
+        loop {
+            let (kind, payload) = receive(&mut self.stream).map_err(|e| format!("connection lost after {} jobs ({})", done, e))?;
+            match kind {
+                JOB => {
+                    let job = Job::decode(&payload)?;
+                    let pixels = render(&job);
+                    send(&mut self.stream, RESULT, &encode_result(job.id, &pixels))?;
+                    done += 1;
+                }
+                DONE => { eprintln!("[worker] finished, {} jobs", done); return Ok(()); }
+                other => return Err(format!("unexpected message {}", other)),
+            }
+        }
+    }
+}
+
+// La carpeta con la copia de la escena se borra al terminar.
+impl Drop for Worker {
+    fn drop(&mut self) { let _ = std::fs::remove_dir_all(&self.dir); }
+}
+
+// Trabajos pendientes; `finished` se activa cuando llegaron todos los resultados y `workers`
+// cuenta los trabajadores conectados.
+struct Queue { pending: Mutex<VecDeque<Job>>, finished: AtomicBool, workers: AtomicUsize }
+
+// Un hilo por trabajador: le manda la escena y le va dando trabajos de a uno.
+fn serve_worker(mut stream: TcpStream, bundle: Arc<Vec<u8>>, queue: Arc<Queue>, results: mpsc::Sender<(Job, Vec<Color>)>, timeout: Duration) {
+    let peer = stream.peer_addr().map_or("?".to_string(), |a| a.to_string());
+    let _ = stream.set_nodelay(true);
+    let _ = stream.set_read_timeout(Some(timeout));
+    if let Err(err) = send(&mut stream, SCENE, &bundle) { eprintln!("[coordinator] {}: {}", peer, err); return; }
+    eprintln!("[coordinator] worker {} joined", peer);
+    queue.workers.fetch_add(1, Ordering::AcqRel);
+    serve_jobs(&mut stream, &peer, &queue, &results);
+    queue.workers.fetch_sub(1, Ordering::AcqRel);
+}
+
+fn serve_jobs(stream: &mut TcpStream, peer: &str, queue: &Queue, results: &mpsc::Sender<(Job, Vec<Color>)>) {
+    loop {
+        let next = queue.pending.lock().unwrap().pop_front();
+        let Some(job) = next else {
+            if queue.finished.load(Ordering::Acquire) { let _ = send(stream, DONE, &[]); return; }
+            std::thread::sleep(Duration::from_millis(50));
+            continue;
+        };
+        let result = send(stream, JOB, &job.encode()).and_then(|_| {
+            let (kind, payload) = receive(stream)?;
+            if kind != RESULT { return Err(format!("expected result, got message {}", kind)); }
+            decode_result(&payload, &job)
+        });
+        match result {
+            Ok(pixels) => { let _ = results.send((job, pixels)); }
+            Err(err) => {
+                eprintln!("\n[coordinator] worker {} dropped ({}), job {} back in the queue", peer, err, job.id);
+                queue.pending.lock().unwrap().push_front(job);
+                return;
+            }
+        }
+    }
+}
+
+// Escucha en `--coordinator`, reparte `jobs` entre los trabajadores que se conecten y llama a
+// `on_result` con cada bloque terminado, en el orden en que llegan. Vuelve cuando están todos.
+// `--job-timeout` es cuánto se espera un resultado antes de dar al trabajador por caído, y también
+// cuánto se espera sin ningún trabajador conectado antes de rendirse.
+pub fn coordinate(cfg: &Config, fingerprint: u64, jobs: Vec<Job>, mut on_result: impl FnMut(&Job, Vec<Color>)) -> Result<(), String> {
+    let addr = cfg.get_str("coordinator").ok_or("missing --coordinator address")?;
+    let timeout = Duration::from_secs_f32(cfg.get_f32("job_timeout", 120.0).max(1.0));
+    let bundle = bundle_scene(cfg, fingerprint)?;
+    let listener = TcpListener::bind(addr).map_err(|e| format!("{}: {}", addr, e))?;
+    let total = jobs.len();
+    eprintln!("[coordinator] listening on {}, {} jobs", addr, total);
+    let queue = Arc::new(Queue { pending: Mutex::new(jobs.into()), finished: AtomicBool::new(false), workers: AtomicUsize::new(0) });
+    let bundle = Arc::new(bundle);
+    let (tx, rx) = mpsc::channel();
+    {
+        let queue = queue.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (bundle, queue, tx) = (bundle.clone(), queue.clone(), tx.clone());
+                std::thread::spawn(move || serve_worker(stream, bundle, queue, tx, timeout));
+            }
+        });
+    }
+    let progress = Progress::new(true);
+    progress.start(total);
+    let mut received = 0;
+    let mut alone_since = Instant::now();
+    while received < total {
+        match rx.recv_timeout(Duration::from_millis(500)) {
+            Ok((job, pixels)) => {
+                on_result(&job, pixels);
+                progress.tile_done();
+                received += 1;
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(err) => return Err(err.to_string()),
+        }
+        if queue.workers.load(Ordering::Acquire) > 0 {
+            alone_since = Instant::now();
+        } else if alone_since.elapsed() > timeout {
+            queue.finished.store(true, Ordering::Release);
+            return Err(format!("no workers for {:.0}s, {} of {} jobs left", timeout.as_secs_f32(), total - received, total));
+        }
+    }
+    queue.finished.store(true, Ordering::Release);
+    // un momento para que los hilos despidan a sus trabajadores
+    std::thread::sleep(Duration::from_millis(200));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job() -> Job {
+        Job {
+            id: 7, width: 64, height: 48, rect: (16, 8, 32, 24), time: 1.25, hours: 17.5, seed: 0xDEAD_BEEF_1234, fov: 55.0, focus_distance: 9.5,
+            position: Vec3::new(1.0, 2.0, -3.0), basis: (Vec3::new(0.0, 0.0, 1.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)),
+        }
+    }
+
+    #[test]
+    fn job_and_result_round_trip_over_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let worker = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let (kind, payload) = receive(&mut stream).unwrap();
+            assert_eq!(kind, JOB);
+            let job = Job::decode(&payload).unwrap();
+            let pixels: Vec<Color> = job.pixels().map(|i| Color::new(i as f32, 0.5, -1.0)).collect();
+            send(&mut stream, RESULT, &encode_result(job.id, &pixels)).unwrap();
+            job
+        });
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let sent = job();
+        send(&mut stream, JOB, &sent.encode()).unwrap();
+        let (kind, payload) = receive(&mut stream).unwrap();
+        assert_eq!(kind, RESULT);
+        let pixels = decode_result(&payload, &sent).unwrap();
+        let got = worker.join().unwrap();
+        assert_eq!(got.encode(), sent.encode());
+        assert_eq!((got.id, got.rect, got.seed), (7, (16, 8, 32, 24), 0xDEAD_BEEF_1234));
+        assert_eq!(pixels.len(), 32 * 24);
+        for (c, i) in pixels.iter().zip(sent.pixels()) {
+            assert_eq!((c.r, c.g, c.b), (i as f32, 0.5, -1.0));
+        }
+        assert_eq!(sent.pixels().next(), Some(8 * 64 + 16));
+        assert_eq!(sent.pixels().last(), Some(31 * 64 + 47));
+    }
+
+    #[test]
+    fn decode_rejects_rects_outside_the_image() {
+        for rect in [(60, 0, 8, 8), (0, 0, 0, 8), (u32::MAX, 0, 2, 8), (0, 40, 8, u32::MAX)] {
+            let bad = Job { rect, ..job() };
+            assert!(Job::decode(&bad.encode()).is_err(), "{:?}", rect);
+        }
+        assert!(Job::decode(&job().encode()[..20]).is_err());
+    }
+
+    #[test]
+    fn result_with_wrong_size_is_rejected() {
+        let pixels = vec![Color::black(); 10];
+        assert!(decode_result(&encode_result(7, &pixels), &job()).is_err());
+    }
+
+    #[test]
+    fn coordinator_gives_up_without_workers() {
+        let cfg = Config::from_entries(vec![("coordinator".into(), "127.0.0.1:0".into()), ("job_timeout".into(), "1".into())]);
+        let started = Instant::now();
+        let result = coordinate(&cfg, 0, vec![job()], |_, _| panic!("no worker should answer"));
+        assert!(result.unwrap_err().contains("no workers"));
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,61 @@
+use crate::config::Config;
+
+// Resolución dinámica: mide cuánto tarda cada cuadro y ajusta la escala de trazado para llegar
+// a los cuadros por segundo pedidos. Si ya está en la escala mínima y sigue lento, baja también
+// la calidad (profundidad de rebotes y muestras). Con la cámara quieta vuelve a la resolución
+// completa, aunque tarde más, para que la imagen fija se vea nítida.
+pub struct DynamicResolution {
+    pub enabled: bool,
+    target: f32,      // segundos por cuadro
+    min_scale: f32,
+    max_scale: f32,
+    scale: f32,
+    quality: f32,     // 1 = profundidad y muestras completas
+    min_quality: f32,
+    still_frames: u32,
+}
+
+impl DynamicResolution {
+    pub fn from_config(cfg: &Config, max_scale: f32) -> Self {
+        let fps = cfg.get_f32("target_fps", 0.0);
+        Self {
+            enabled: fps > 0.0,
+            target: 1.0 / fps.max(1.0),
+            min_scale: cfg.get_f32("min_render_scale", 0.25).clamp(0.05, max_scale),
+            max_scale,
+            scale: max_scale,
+            quality: 1.0,
+            min_quality: if cfg.get_bool("dynamic_quality", true) { 0.25 } else { 1.0 },
+            still_frames: 0,
+        }
+    }
+
+    pub fn scale(&self) -> f32 { if self.enabled { self.scale } else { self.max_scale } }
+
+    // Profundidad de recursión y muestras reducidas según la calidad actual.
+    pub fn max_depth(&self, full: i32) -> i32 { (1 + ((full - 1) as f32 * self.quality).round() as i32).min(full) }
+    pub fn samples(&self, full: u32) -> u32 { if full == 0 { 0 } else { ((full as f32 * self.quality).round() as u32).max(1) } }
+
+    // `frame_time` es lo que tardó en trazarse el último cuadro; `moving` si la cámara cambió.
+    pub fn update(&mut self, frame_time: f32, moving: bool) {
+        if !self.enabled { return; }
+        if !moving {
+            // unos cuadros quieta y se sube directo a la calidad completa
+            self.still_frames += 1;
+            if self.still_frames >= 3 { self.scale = self.max_scale; self.quality = 1.0; }
+            return;
+        }
+        self.still_frames = 0;
+        // el costo crece con el área: la escala se corrige con la raíz de la razón de tiempos,
+        // limitada por cuadro para que no oscile
+        let ratio = (self.target / frame_time.max(1e-4)).sqrt().clamp(0.8, 1.15);
+        if (ratio - 1.0).abs() < 0.03 { return; }
+        if ratio < 1.0 && self.scale <= self.min_scale {
+            self.quality = (self.quality * ratio).max(self.min_quality);
+        } else if ratio > 1.0 && self.quality < 1.0 {
+            self.quality = (self.quality * ratio).min(1.0);
+        } else {
+            self.scale = (self.scale * ratio).clamp(self.min_scale, self.max_scale);
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use crate::color::{Color, Vec3};
+use std::f32::consts::PI;
+
+// Panorama equirectangular HDR para iluminación basada en imagen.
+// Convención: u = 0.5 + atan2(z, x) / 2π, v = acos(y) / π (v = 0 arriba).
+pub struct EnvMap {
+    levels: Vec<EnvLevel>, // 0 = resolución completa; los demás, promedios 2x2 para reflejos difusos
+    rotation: f32,         // radianes alrededor de +Y
+    intensity: f32,
+    sh: [Color; 9],        // irradiancia en armónicos esféricos de orden 2
+    row_cdf: Vec<f32>,     // distribución marginal por filas (luminancia * sen θ)
+    col_cdf: Vec<f32>,     // distribución condicional por fila, w valores por fila
+    total_weight: f32,
+}
+
+struct EnvLevel { w: u32, h: u32, data: Vec<Color> }
+
+impl EnvLevel {
+    fn texel(&self, x: i32, y: i32) -> Color {
+        let x = x.rem_euclid(self.w as i32) as u32;
+        let y = y.clamp(0, self.h as i32 - 1) as u32;
+        self.data[(y * self.w + x) as usize]
+    }
+    fn bilinear(&self, u: f32, v: f32) -> Color {
+        let fx = u * self.w as f32 - 0.5;
+        let fy = v * self.h as f32 - 0.5;
+        let (x0, y0) = (fx.floor() as i32, fy.floor() as i32);
+        let (tx, ty) = (fx - x0 as f32, fy - y0 as f32);
+        let top = self.texel(x0, y0) * (1.0 - tx) + self.texel(x0 + 1, y0) * tx;
+        let bottom = self.texel(x0, y0 + 1) * (1.0 - tx) + self.texel(x0 + 1, y0 + 1) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+}
+
+fn luminance(c: Color) -> f32 { 0.2126 * c.r + 0.7152 * c.g + 0.0722 * c.b }
+
+fn sh_basis(d: Vec3) -> [f32; 9] {
+    [
+        0.282095,
+        0.488603 * d.y, 0.488603 * d.z, 0.488603 * d.x,
+        1.092548 * d.x * d.y, 1.092548 * d.y * d.z, 0.315392 * (3.0 * d.z * d.z - 1.0),
+        1.092548 * d.x * d.z, 0.546274 * (d.x * d.x - d.y * d.y),
+    ]
+}
+
+impl EnvMap {
+    pub fn load(path: &str, rotation_deg: f32, intensity: f32) -> Result<Self, String> {
+        let lower = path.to_lowercase();
+        let tex = if lower.ends_with(".exr") {
+            crate::exr::read_exr(path)?
+        } else if lower.ends_with(".hdr") || lower.ends_with(".pic") {
+            crate::hdr::read_hdr(path)?
+        } else {
+            return Err(format!("{}: panoramas must be Radiance .hdr or OpenEXR .exr", path));
+        };
+        Ok(Self::from_pixels(tex.w, tex.h, tex.data, rotation_deg, intensity))
+    }
+
+    pub fn from_pixels(w: u32, h: u32, data: Vec<Color>, rotation_deg: f32, intensity: f32) -> Self {
+        let mut levels = vec![EnvLevel { w, h, data }];
+        // se para antes de que algún lado llegue a 0 (panoramas que no son 2:1)
+        while levels.last().is_some_and(|l| l.h >= 8 && l.w >= 2) {
+            let prev = levels.last().unwrap();
+            let (nw, nh) = (prev.w / 2, prev.h / 2);
+            let mut data = Vec::with_capacity((nw * nh) as usize);
+            for y in 0..nh as i32 { for x in 0..nw as i32 {
+                let c = prev.texel(2 * x, 2 * y) + prev.texel(2 * x + 1, 2 * y) + prev.texel(2 * x, 2 * y + 1) + prev.texel(2 * x + 1, 2 * y + 1);
+                data.push(c * 0.25);
+            }}
+            levels.push(EnvLevel { w: nw, h: nh, data });
+        }
+
+        // irradiancia SH y CDFs para muestreo por importancia, ambos sobre el nivel 0
+        let base = &levels[0];
+        let mut sh = [Color::black(); 9];
+        let mut row_cdf = Vec::with_capacity(h as usize);
+        let mut col_cdf = Vec::with_capacity((w * h) as usize);
+        let mut total = 0.0;
+        let d_omega = (2.0 * PI / w as f32) * (PI / h as f32);
+        for y in 0..h {
+            let theta = (y as f32 + 0.5) / h as f32 * PI;
+            let sin_t = theta.sin();
+            let mut row_sum = 0.0;
+            for x in 0..w {
+                let c = base.data[(y * w + x) as usize];
+                let phi = ((x as f32 + 0.5) / w as f32 - 0.5) * 2.0 * PI;
+                let d = Vec3::new(sin_t * phi.cos(), theta.cos(), sin_t * phi.sin());
+                let basis = sh_basis(d);
+                for (k, b) in basis.iter().enumerate() { sh[k] = sh[k] + c * (b * sin_t * d_omega); }
+                row_sum += luminance(c).max(1e-6) * sin_t;
+                col_cdf.push(row_sum);
+            }
+            total += row_sum;
+            row_cdf.push(total);
+        }
+        // convolución con el lóbulo coseno (Ramamoorthi & Hanrahan)
+        let band = [PI, 2.0 * PI / 3.0, 2.0 * PI / 3.0, 2.0 * PI / 3.0, PI / 4.0, PI / 4.0, PI / 4.0, PI / 4.0, PI / 4.0];
+        for k in 0..9 { sh[k] = sh[k] * band[k]; }
+
+        Self { levels, rotation: rotation_deg.to_radians(), intensity, sh, row_cdf, col_cdf, total_weight: total }
+    }
+
+    // Dirección del mundo -> dirección en el espacio del panorama (y viceversa).
+    fn to_local(&self, d: Vec3) -> Vec3 {
+        let (s, c) = self.rotation.sin_cos();
+        Vec3::new(d.x * c + d.z * s, d.y, -d.x * s + d.z * c)
+    }
+    fn to_world(&self, d: Vec3) -> Vec3 {
+        let (s, c) = self.rotation.sin_cos();
+        Vec3::new(d.x * c - d.z * s, d.y, d.x * s + d.z * c)
+    }
+    fn dir_to_uv(d: Vec3) -> (f32, f32) {
+        let u = 0.5 + d.z.atan2(d.x) / (2.0 * PI);
+        let v = d.y.clamp(-1.0, 1.0).acos() / PI;
+        (u, v)
+    }
+
+    pub fn radiance(&self, dir: Vec3) -> Color {
+        let (u, v) = Self::dir_to_uv(self.to_local(dir.normalized()));
+        self.levels[0].bilinear(u, v) * self.intensity
+    }
+
+    // Radiancia prefiltrada para un lóbulo de Phong con exponente `shininess`.
+    pub fn glossy(&self, dir: Vec3, shininess: f32) -> Color {
+        let spread = (2.0 / (shininess + 2.0)).sqrt(); // apertura aproximada del lóbulo, en radianes
+        let level = (self.levels[0].h as f32 * spread / PI).max(1.0).log2().min((self.levels.len() - 1) as f32);
+        let (u, v) = Self::dir_to_uv(self.to_local(dir.normalized()));
+        let l0 = level.floor() as usize;
+        let l1 = (l0 + 1).min(self.levels.len() - 1);
+        let k = level - l0 as f32;
+        (self.levels[l0].bilinear(u, v) * (1.0 - k) + self.levels[l1].bilinear(u, v) * k) * self.intensity
+    }
+
+    // Irradiancia / π: la radiancia que reflejaría una superficie difusa blanca con normal `n`.
+    pub fn diffuse(&self, n: Vec3) -> Color {
+        let b = sh_basis(self.to_local(n));
+        let mut e = Color::black();
+        for (c, bk) in self.sh.iter().zip(b) { e = e + *c * bk; }
+        Color::new(e.r.max(0.0), e.g.max(0.0), e.b.max(0.0)) * (self.intensity / PI)
+    }
+
+    // Elige una dirección proporcional a la luminancia del panorama.
+    // Devuelve (dirección, radiancia, pdf por ángulo sólido).
+    pub fn sample(&self, u1: f32, u2: f32) -> (Vec3, Color, f32) {
+        let (w, h) = (self.levels[0].w as usize, self.levels[0].h as usize);
+        let target = u1 * self.total_weight;
+        let y = self.row_cdf.partition_point(|&c| c < target).min(h - 1);
+        let row = &self.col_cdf[y * w..(y + 1) * w];
+        let row_total = row[w - 1];
+        let x = row.partition_point(|&c| c < u2 * row_total).min(w - 1);
+        // posición continua dentro del texel elegido
+        let row_start = if y > 0 { self.row_cdf[y - 1] } else { 0.0 };
+        let fy = ((target - row_start) / (self.row_cdf[y] - row_start).max(1e-12)).clamp(0.0, 1.0);
+        let col_start = if x > 0 { row[x - 1] } else { 0.0 };
+        let fx = ((u2 * row_total - col_start) / (row[x] - col_start).max(1e-12)).clamp(0.0, 1.0);
+        let u = (x as f32 + fx) / w as f32;
+        let v = (y as f32 + fy) / h as f32;
+        let theta = v * PI;
+        let phi = (u - 0.5) * 2.0 * PI;
+        let local = Vec3::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin());
+        let dir = self.to_world(local);
+        (dir, self.radiance(dir), self.pdf_texel(x, y, theta.sin()))
+    }
+
+    fn pdf_texel(&self, x: usize, y: usize, sin_theta: f32) -> f32 {
+        let (w, h) = (self.levels[0].w as usize, self.levels[0].h as usize);
+        if sin_theta <= 1e-6 { return 0.0; }
+        let row = &self.col_cdf[y * w..(y + 1) * w];
+        let weight = row[x] - if x > 0 { row[x - 1] } else { 0.0 };
+        let p_uv = weight / self.total_weight * (w * h) as f32;
+        p_uv / (2.0 * PI * PI * sin_theta)
+    }
+}
@@ -0,0 +1,243 @@
+use std::io::Write;
+use crate::color::Color;
+use crate::texture::LoadedTexture;
+
+// Escritor mínimo de OpenEXR: una sola parte, líneas sin compresión, canales FLOAT de 32 bits.
+// Alcanza para pasar la imagen lineal y los AOV a un compositor.
+pub fn write_exr(path: &str, width: u32, height: u32, channels: &[(String, Vec<f32>)]) -> Result<(), String> {
+    let n = (width * height) as usize;
+    if let Some((name, _)) = channels.iter().find(|(_, data)| data.len() != n) {
+        return Err(format!("{}: channel {} has the wrong size", path, name));
+    }
+    // el formato exige los canales en orden alfabético, tanto en la cabecera como en los datos
+    let mut order: Vec<&(String, Vec<f32>)> = channels.iter().collect();
+    order.sort_by(|a, b| a.0.as_bytes().cmp(b.0.as_bytes()));
+
+    let mut out: Vec<u8> = Vec::new();
+    out.extend_from_slice(&[0x76, 0x2f, 0x31, 0x01]); // número mágico
+    out.extend_from_slice(&2u32.to_le_bytes());        // versión 2, imagen de líneas
+    let mut attr = |name: &str, kind: &str, value: &[u8]| {
+        out.extend_from_slice(name.as_bytes()); out.push(0);
+        out.extend_from_slice(kind.as_bytes()); out.push(0);
+        out.extend_from_slice(&(value.len() as i32).to_le_bytes());
+        out.extend_from_slice(value);
+    };
+    let mut chlist = Vec::new();
+    for (name, _) in &order {
+        chlist.extend_from_slice(name.as_bytes()); chlist.push(0);
+        chlist.extend_from_slice(&2i32.to_le_bytes()); // FLOAT
+        chlist.extend_from_slice(&[0, 0, 0, 0]);       // pLinear + reservado
+        chlist.extend_from_slice(&1i32.to_le_bytes()); // muestreo x
+        chlist.extend_from_slice(&1i32.to_le_bytes()); // muestreo y
+    }
+    chlist.push(0);
+    let window: Vec<u8> = [0i32, 0, width as i32 - 1, height as i32 - 1].iter().flat_map(|v| v.to_le_bytes()).collect();
+    attr("channels", "chlist", &chlist);
+    attr("compression", "compression", &[0]);
+    attr("dataWindow", "box2i", &window);
+    attr("displayWindow", "box2i", &window);
+    attr("lineOrder", "lineOrder", &[0]);
+    attr("pixelAspectRatio", "float", &1.0f32.to_le_bytes());
+    attr("screenWindowCenter", "v2f", &[0u8; 8]);
+    attr("screenWindowWidth", "float", &1.0f32.to_le_bytes());
+    out.push(0); // fin de la cabecera
+
+    // tabla de desplazamientos: un bloque por línea
+    let line_bytes = width as usize * 4 * order.len();
+    let table_start = out.len();
+    let first_block = table_start + 8 * height as usize;
+    for y in 0..height as usize {
+        out.extend_from_slice(&((first_block + y * (8 + line_bytes)) as u64).to_le_bytes());
+    }
+    for y in 0..height as usize {
+        out.extend_from_slice(&(y as i32).to_le_bytes());
+        out.extend_from_slice(&(line_bytes as i32).to_le_bytes());
+        for (_, data) in &order {
+            for v in &data[y * width as usize..(y + 1) * width as usize] { out.extend_from_slice(&v.to_le_bytes()); }
+        }
+    }
+    let mut file = std::fs::File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+    file.write_all(&out).map_err(|e| format!("{}: {}", path, e))
+}
+
+// Medio flotante (IEEE 754 binary16) a f32.
+fn half_to_f32(h: u16) -> f32 {
+    let sign = if h & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exp = ((h >> 10) & 0x1f) as i32;
+    let mant = (h & 0x3ff) as f32;
+    match exp {
+        0 => sign * mant * 2f32.powi(-24),
+        31 => if mant == 0.0 { sign * f32::INFINITY } else { f32::NAN },
+        _ => sign * (1.0 + mant / 1024.0) * 2f32.powi(exp - 15),
+    }
+}
+
+// Lector de lo que escribe `write_exr` y poco más: una sola parte, líneas sin compresión y canales
+// HALF o FLOAT. Toma R, G y B (o Y, en escala de grises); lo comprimido se rechaza con un error.
+pub fn read_exr(path: &str) -> Result<LoadedTexture, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    let err = |msg: String| format!("{}: {}", path, msg);
+    let get = |pos: usize, n: usize| bytes.get(pos..pos + n).ok_or_else(|| err("truncated file".into()));
+    let i32_at = |pos: usize| -> Result<i32, String> { Ok(i32::from_le_bytes(get(pos, 4)?.try_into().unwrap())) };
+    let cstr = |pos: &mut usize| -> Result<String, String> {
+        let rest = bytes.get(*pos..).unwrap_or_default();
+        let len = rest.iter().position(|&b| b == 0).ok_or_else(|| err("truncated header".into()))?;
+        let s = String::from_utf8_lossy(&bytes[*pos..*pos + len]).into_owned();
+        *pos += len + 1;
+        Ok(s)
+    };
+    if get(0, 4)? != [0x76, 0x2f, 0x31, 0x01] { return Err(err("not an OpenEXR file".into())); }
+    // bits de la versión: 0x200 en bloques, 0x800 profundo, 0x1000 varias partes
+    if i32_at(4)? & 0x1a00 != 0 { return Err(err("only single-part scanline EXR files are supported".into())); }
+
+    let mut pos = 8;
+    let mut channels: Vec<(String, i32)> = Vec::new();
+    let (mut compression, mut window) = (None, None);
+    loop {
+        let name = cstr(&mut pos)?;
+        if name.is_empty() { break; }
+        let _kind = cstr(&mut pos)?;
+        let size = i32_at(pos)?.max(0) as usize;
+        let value = get(pos + 4, size)?;
+        pos += 4 + size;
+        match name.as_str() {
+            "channels" => {
+                let mut p = 0;
+                while p < value.len() && value[p] != 0 {
+                    let len = value[p..].iter().position(|&b| b == 0).ok_or_else(|| err("bad channel list".into()))?;
+                    let ch = String::from_utf8_lossy(&value[p..p + len]).into_owned();
+                    let kind = value.get(p + len + 1..p + len + 5).ok_or_else(|| err("bad channel list".into()))?;
+                    channels.push((ch, i32::from_le_bytes(kind.try_into().unwrap())));
+                    p += len + 1 + 16;
+                }
+            }
+            "compression" => compression = value.first().copied(),
+            "dataWindow" if size == 16 => window = Some([0, 4, 8, 12].map(|o| i32::from_le_bytes(value[o..o + 4].try_into().unwrap()))),
+            _ => {}
+        }
+    }
+    match compression {
+        Some(0) => {}
+        Some(c) => return Err(err(format!("compressed EXR (method {}) is not supported; save it uncompressed or as .hdr", c))),
+        None => return Err(err("missing compression attribute".into())),
+    }
+    let [x0, y0, x1, y1] = window.ok_or_else(|| err("missing dataWindow".into()))?;
+    let (w, h) = ((x1 - x0 + 1).max(0) as usize, (y1 - y0 + 1).max(0) as usize);
+    if w == 0 || h == 0 { return Err(err("empty image".into())); }
+    // tamaño de cada muestra: UINT y FLOAT ocupan 4 bytes, HALF 2
+    let sizes: Vec<usize> = channels.iter().map(|(_, t)| if *t == 1 { 2 } else { 4 }).collect();
+    let find = |names: &[&str]| channels.iter().position(|(n, t)| names.contains(&n.as_str()) && *t != 0);
+    let rgb = match (find(&["R"]), find(&["G"]), find(&["B"]), find(&["Y"])) {
+        (Some(r), Some(g), Some(b), _) => [r, g, b],
+        (_, _, _, Some(y)) => [y, y, y],
+        _ => return Err(err("needs R, G and B (or Y) channels in HALF or FLOAT".into())),
+    };
+
+    let line_bytes: usize = sizes.iter().map(|s| s * w).sum();
+    let mut data = vec![Color::black(); w * h];
+    for line in 0..h {
+        let offset = u64::from_le_bytes(get(pos + line * 8, 8)?.try_into().unwrap()) as usize;
+        let y = i32_at(offset)? - y0;
+        if y < 0 || y as usize >= h || i32_at(offset + 4)? as usize != line_bytes { return Err(err(format!("bad scanline block {}", line))); }
+        let block = get(offset + 8, line_bytes)?;
+        let mut start = 0;
+        let mut values = [[].as_slice(); 3];
+        for (c, size) in sizes.iter().enumerate() {
+            for (k, &ch) in rgb.iter().enumerate() { if ch == c { values[k] = &block[start..start + size * w]; } }
+            start += size * w;
+        }
+        let sample = |k: usize, x: usize| if sizes[rgb[k]] == 2 {
+            half_to_f32(u16::from_le_bytes(values[k][x * 2..x * 2 + 2].try_into().unwrap()))
+        } else {
+            f32::from_le_bytes(values[k][x * 4..x * 4 + 4].try_into().unwrap())
+        };
+        for x in 0..w { data[y as usize * w + x] = Color::new(sample(0, x), sample(1, x), sample(2, x)); }
+    }
+    eprintln!("[exr] loaded {} ({}x{})", path, w, h);
+    Ok(LoadedTexture { w: w as u32, h: h as u32, data, anim: None })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp(name: &str) -> String {
+        std::env::temp_dir().join(format!("proyecto2-{}-{}.exr", name, std::process::id())).to_str().unwrap().to_string()
+    }
+
+    fn read_bytes(name: &str, bytes: &[u8]) -> Result<LoadedTexture, String> {
+        let path = temp(name);
+        std::fs::write(&path, bytes).unwrap();
+        let tex = read_exr(&path);
+        let _ = std::fs::remove_file(&path);
+        tex
+    }
+
+    // Archivo de una línea con un canal Y en HALF, armado a mano.
+    fn half_gray(values: &[u16], compression: u8) -> Vec<u8> {
+        let mut out = vec![0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0];
+        let mut attr = |name: &str, kind: &str, value: &[u8]| {
+            out.extend_from_slice(format!("{}\0{}\0", name, kind).as_bytes());
+            out.extend_from_slice(&(value.len() as i32).to_le_bytes());
+            out.extend_from_slice(value);
+        };
+        let mut chlist = b"Y\0".to_vec();
+        for v in [1i32, 0, 1, 1] { chlist.extend_from_slice(&v.to_le_bytes()); }
+        chlist.push(0);
+        let window: Vec<u8> = [0i32, 0, values.len() as i32 - 1, 0].iter().flat_map(|v| v.to_le_bytes()).collect();
+        attr("channels", "chlist", &chlist);
+        attr("compression", "compression", &[compression]);
+        attr("dataWindow", "box2i", &window);
+        out.push(0);
+        let block = out.len() as u64 + 8;
+        out.extend_from_slice(&block.to_le_bytes());
+        out.extend_from_slice(&0i32.to_le_bytes());
+        out.extend_from_slice(&(values.len() as i32 * 2).to_le_bytes());
+        for v in values { out.extend_from_slice(&v.to_le_bytes()); }
+        out
+    }
+
+    #[test]
+    fn float_channels_round_trip() {
+        let (w, h) = (3u32, 2u32);
+        let channel = |k: f32| (0..w * h).map(|i| i as f32 * k - 1.5).collect::<Vec<f32>>();
+        // fuera de orden alfabético y con un canal que el lector ignora
+        let channels = vec![("R".to_string(), channel(1.0)), ("Z".to_string(), channel(100.0)), ("B".to_string(), channel(0.25)), ("G".to_string(), channel(-3.0))];
+        let path = temp("float");
+        write_exr(&path, w, h, &channels).unwrap();
+        let tex = read_exr(&path);
+        let _ = std::fs::remove_file(&path);
+        let tex = tex.unwrap();
+        assert_eq!((tex.w, tex.h), (w, h));
+        for (i, c) in tex.data.iter().enumerate() {
+            assert_eq!((c.r, c.g, c.b), (channels[0].1[i], channels[3].1[i], channels[2].1[i]));
+        }
+        assert!(write_exr(&path, w, h, &[("R".to_string(), vec![0.0; 5])]).is_err());
+    }
+
+    #[test]
+    fn reads_half_gray_images() {
+        assert_eq!(half_to_f32(0x3c00), 1.0);
+        assert_eq!(half_to_f32(0xc000), -2.0);
+        assert_eq!(half_to_f32(0x7bff), 65504.0);
+        assert_eq!(half_to_f32(0x0001), 2f32.powi(-24));
+        assert_eq!(half_to_f32(0x7c00), f32::INFINITY);
+        assert!(half_to_f32(0x7e00).is_nan());
+        let tex = read_bytes("half", &half_gray(&[0x3800, 0x4500], 0)).unwrap();
+        assert_eq!((tex.w, tex.h), (2, 1));
+        assert_eq!((tex.data[0].r, tex.data[0].g, tex.data[0].b), (0.5, 0.5, 0.5));
+        assert_eq!(tex.data[1].b, 5.0);
+    }
+
+    #[test]
+    fn rejects_compressed_and_foreign_files() {
+        let err = read_bytes("zip", &half_gray(&[0x3c00], 3)).err().unwrap();
+        assert!(err.contains("compressed EXR (method 3)"), "{}", err);
+        assert!(read_bytes("hdr", b"#?RADIANCE\n").err().unwrap().contains("not an OpenEXR file"));
+        let mut tiled = half_gray(&[0x3c00], 0);
+        tiled[5] |= 0x02;
+        assert!(read_bytes("tiled", &tiled).err().unwrap().contains("single-part scanline"));
+        let full = half_gray(&[0x3c00, 0x3c00], 0);
+        assert!(read_bytes("short", &full[..full.len() - 2]).is_err());
+    }
+}
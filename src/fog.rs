@@ -0,0 +1,87 @@
+use crate::color::{Color, Vec3};
+use crate::config::Config;
+use crate::clouds::{hash3, henyey_greenstein};
+use crate::ray_intersect::Ray;
+use crate::Scene;
+
+// Medio participante: niebla por distancia, niebla baja que se adelgaza con la altura y
+// luz volumétrica (rayos de sol entre las hojas y las nubes) por dispersión simple.
+// Densidad en un punto: density + height_density * exp(-falloff * (y - height)).
+pub struct Fog {
+    pub enabled: bool,
+    density: f32,
+    height_density: f32,
+    height: f32,
+    falloff: f32,
+    anisotropy: f32, // g de Henyey-Greenstein: > 0 dispersa hacia adelante
+    far: f32,        // distancia de niebla para rayos que no chocan con nada
+    steps: u32,      // muestras de sombra por rayo primario (0 = sin rayos volumétricos)
+}
+
+impl Fog {
+    pub fn from_config(cfg: &Config, ground_y: f32) -> Self {
+        Self {
+            enabled: cfg.get_bool("fog", false),
+            density: cfg.get_f32("fog_density", 0.006).max(0.0),
+            height_density: cfg.get_f32("fog_height_density", 0.04).max(0.0),
+            height: cfg.get_f32("fog_height", ground_y),
+            falloff: cfg.get_f32("fog_falloff", 0.3).max(1e-3),
+            anisotropy: cfg.get_f32("fog_anisotropy", 0.6).clamp(-0.95, 0.95),
+            far: cfg.get_f32("fog_far", 80.0),
+            steps: cfg.get_u32("god_rays", 8),
+        }
+    }
+
+    fn density_at(&self, y: f32) -> f32 {
+        self.density + self.height_density * (-self.falloff * (y - self.height)).exp()
+    }
+
+    // Profundidad óptica entre `origin` y `origin + dir * t`, integrada analíticamente.
+    fn optical_depth(&self, origin: Vec3, dir: Vec3, t: f32, uniform: bool) -> f32 {
+        let k = self.falloff * dir.y;
+        let base = self.height_density * (-self.falloff * (origin.y - self.height)).exp();
+        let height = if k.abs() < 1e-4 { base * t } else { base * (1.0 - (-k * t).exp()) / k };
+        if uniform { self.density * t + height } else { height }
+    }
+
+    // Fracción de lo que está a distancia `t_hit` que llega a través de la niebla.
+    pub fn transmittance(&self, ray: &Ray, t_hit: f32) -> f32 {
+        if !self.enabled { return 1.0; }
+        let hit = t_hit.is_finite();
+        (-self.optical_depth(ray.origin, ray.dir, if hit { t_hit } else { self.far }, hit)).exp()
+    }
+
+    // Aplica la niebla a `color`, visto a distancia `t_hit` (infinita si el rayo no chocó).
+    // Con `god_rays` se marcha el rayo probando sombras de vóxeles y nubes hacia la luz
+    // principal para dibujar los haces de luz.
+    pub fn apply(&self, ray: &Ray, t_hit: f32, color: Color, scene: &Scene, god_rays: bool) -> Color {
+        if !self.enabled { return color; }
+        let (origin, dir) = (ray.origin, ray.dir);
+        let fog_color = scene.sky.fog_color(dir);
+        // el cielo ya trae su propia perspectiva aérea: solo la niebla baja lo tapa
+        let hit = t_hit.is_finite();
+        let t_end = if hit { t_hit } else { self.far };
+        let transmittance = (-self.optical_depth(origin, dir, t_end, hit)).exp();
+        let mut out = color * transmittance + fog_color * (1.0 - transmittance);
+        if !god_rays || self.steps == 0 { return out; }
+
+        let (to_light, light_col) = scene.sky.key_light();
+        if light_col.r + light_col.g + light_col.b <= 1e-4 { return out; }
+        let phase = henyey_greenstein(dir.dot(to_light), self.anisotropy);
+        let t_march = t_end.min(self.far);
+        let dt = t_march / self.steps as f32;
+        let jitter = hash3((dir.x * 7919.0) as i32, (dir.y * 7919.0) as i32, (dir.z * 7919.0) as i32);
+        let mut inscatter = 0.0;
+        for i in 0..self.steps {
+            let t = (i as f32 + jitter) * dt;
+            let p = origin + dir * t;
+            if scene.world.occluded(p, to_light, 200.0) { continue; }
+            let vis = scene.clouds.transmittance(p, to_light, scene.time);
+            let tr = (-self.optical_depth(origin, dir, t, true)).exp();
+            inscatter += self.density_at(p.y) * tr * vis * dt;
+        }
+        // la parte en sombra ya quedó como niebla ambiente; esto suma solo la luz directa
+        out = out + light_col * (inscatter * phase);
+        out
+    }
+}
@@ -1,23 +1,34 @@
 use raylib::prelude::*;
+use rayon::prelude::*;
 use crate::color::Color as PColor;
 
 pub struct RLFramebuffer {
-    pub width: u32,
+    pub width: u32,            // tamaño en uso, que puede ser menor que la textura
     pub height: u32,
-    img: Image,
+    capacity: (u32, u32),
+    pub filter: TextureFilter, // cómo se estira la imagen si es más chica que la ventana
+    pub status: Option<(String, f32)>, // texto y barra de avance encima de la imagen
     buf: Vec<PColor>,
+    rgba: Vec<u8>,             // buf empaquetado para subirlo a la GPU
+    tex: Option<Texture2D>,    // se crea una vez y se actualiza en cada cuadro
 }
 
 impl RLFramebuffer {
     pub fn new(width: u32, height: u32) -> Self {
-        let img = Image::gen_image_color(width as i32, height as i32, Color::BLACK);
         let buf = vec![PColor::black(); (width * height) as usize];
-        Self { width, height, img, buf }
+        let rgba = vec![0u8; (width * height * 4) as usize];
+        Self { width, height, capacity: (width, height), filter: TextureFilter::TEXTURE_FILTER_POINT, status: None, buf, rgba, tex: None }
+    }
+    // Cambia la resolución en uso sin tocar la textura: se sube solo la esquina que se usa.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        let (width, height) = (width.clamp(1, self.capacity.0), height.clamp(1, self.capacity.1));
+        if (width, height) == (self.width, self.height) { return; }
+        self.width = width;
+        self.height = height;
+        self.buf = vec![PColor::black(); (width * height) as usize];
     }
     pub fn clear(&mut self, col: PColor) {
         for v in self.buf.iter_mut() { *v = col; }
-        let rc: Color = col.into();
-        self.img = Image::gen_image_color(self.width as i32, self.height as i32, rc);
     }
     pub fn set_pixel(&mut self, x: u32, y: u32, c: PColor) {
         if x < self.width && y < self.height {
@@ -25,27 +36,44 @@ impl RLFramebuffer {
             self.buf[idx] = c;
         }
     }
-   
+
     pub fn present(&mut self, rl: &mut RaylibHandle, th: &RaylibThread) {
-    
-        for y in 0..self.height {
-            for x in 0..self.width {
-                let idx = (y * self.width + x) as usize;
-                let rc: Color = self.buf[idx].into();
-                self.img.draw_pixel(x as i32, y as i32, rc);
+        // conversión en paralelo a RGBA8 y una sola subida de toda la imagen
+        let used = (self.width * self.height * 4) as usize;
+        self.rgba[..used].par_chunks_mut(4).zip(self.buf.par_iter()).for_each(|(px, c)| {
+            let [r, g, b] = c.to_rgb8();
+            px.copy_from_slice(&[r, g, b, 255]);
+        });
+        if self.tex.is_none() {
+            let img = Image::gen_image_color(self.capacity.0 as i32, self.capacity.1 as i32, Color::BLACK);
+            match rl.load_texture_from_image(th, &img) {
+                Ok(tex) => self.tex = Some(tex),
+                Err(err) => { eprintln!("[framebuffer] {}", err); return; }
             }
         }
-        if let Ok(tex) = rl.load_texture_from_image(th, &self.img) {
-            let mut d = rl.begin_drawing(th);
-            d.clear_background(Color::BLACK);
-            d.draw_texture(&tex, 0, 0, Color::WHITE);
+        let Some(tex) = self.tex.as_mut() else { return; };
+        let region = Rectangle::new(0.0, 0.0, self.width as f32, self.height as f32);
+        // raylib pide el búfer del tamaño de toda la textura, aunque lea solo la región
+        if let Err(err) = tex.update_texture_rec(region, &self.rgba) { eprintln!("[framebuffer] {}", err); }
+        tex.set_texture_filter(th, self.filter);
+        let (sw, sh) = (rl.get_screen_width() as f32, rl.get_screen_height() as f32);
+        let mut d = rl.begin_drawing(th);
+        d.clear_background(Color::BLACK);
+        d.draw_texture_pro(&*tex, region, Rectangle::new(0.0, 0.0, sw, sh), Vector2::zero(), 0.0, Color::WHITE);
+        if let Some((text, fraction)) = &self.status {
+            let y = sh as i32 - 34;
+            d.draw_rectangle(0, y, sw as i32, 34, Color::new(0, 0, 0, 170));
+            d.draw_rectangle(0, y, (sw * fraction.clamp(0.0, 1.0)) as i32, 4, Color::GREEN);
+            d.draw_text(text, 10, y + 10, 18, Color::WHITE);
         }
     }
 
     pub fn width(&self) -> u32 { self.width }
     pub fn height(&self) -> u32 { self.height }
-    pub fn save(&self, path: &str) { let _ = self.img.export_image(path); }
-  
+    pub fn save(&self, path: &str) {
+        if let Err(err) = save_png(path, self.width, self.height, &self.buf) { eprintln!("[framebuffer] {}", err); }
+    }
+
     pub fn replace_buffer(&mut self, buf: Vec<PColor>) {
         if buf.len() == (self.width * self.height) as usize {
             self.buf = buf;
@@ -55,11 +83,13 @@ impl RLFramebuffer {
 
 impl From<PColor> for Color {
     fn from(c: PColor) -> Self {
-        Color::new(
-            (c.r.clamp(0.0,1.0)*255.0) as u8,
-            (c.g.clamp(0.0,1.0)*255.0) as u8,
-            (c.b.clamp(0.0,1.0)*255.0) as u8,
-            255
-        )
+        let [r, g, b] = c.to_rgb8();
+        Color::new(r, g, b, 255)
     }
 }
+
+// Guarda una imagen sin pasar por raylib (para el render sin ventana).
+pub fn save_png(path: &str, width: u32, height: u32, pixels: &[PColor]) -> Result<(), String> {
+    let bytes: Vec<u8> = pixels.iter().flat_map(|c| c.to_rgb8()).collect();
+    image::save_buffer(path, &bytes, width, height, image::ExtendedColorType::Rgb8).map_err(|e| format!("{}: {}", path, e))
+}
@@ -0,0 +1,142 @@
+use crate::color::Color;
+use crate::texture::LoadedTexture;
+use std::io::{BufRead, BufReader, Read};
+
+// Lector de imágenes Radiance (.hdr, RGBE de 32 bits con o sin RLE por scanline).
+pub fn read_hdr(path: &str) -> Result<LoadedTexture, String> {
+    let file = std::fs::File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut reader = BufReader::new(file);
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|e| e.to_string())?;
+    if !line.starts_with("#?") { return Err(format!("{}: not a Radiance HDR file", path)); }
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).map_err(|e| e.to_string())? == 0 { return Err(format!("{}: truncated header", path)); }
+        let l = line.trim();
+        if l.is_empty() { break; }
+        if let Some(fmt) = l.strip_prefix("FORMAT=") && fmt != "32-bit_rle_rgbe" { return Err(format!("{}: unsupported format {}", path, fmt)); }
+    }
+    line.clear();
+    reader.read_line(&mut line).map_err(|e| e.to_string())?;
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    if parts.len() != 4 || parts[0] != "-Y" || parts[2] != "+X" {
+        return Err(format!("{}: unsupported orientation '{}'", path, line.trim()));
+    }
+    let h: u32 = parts[1].parse().map_err(|_| format!("{}: bad height", path))?;
+    let w: u32 = parts[3].parse().map_err(|_| format!("{}: bad width", path))?;
+
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes).map_err(|e| e.to_string())?;
+    let mut pos = 0usize;
+    let mut next = || -> Result<u8, String> {
+        let b = *bytes.get(pos).ok_or_else(|| format!("{}: unexpected end of data", path))?;
+        pos += 1;
+        Ok(b)
+    };
+    let mut data = Vec::with_capacity((w * h) as usize);
+    let mut scan = vec![[0u8; 4]; w as usize];
+    for _ in 0..h {
+        let b0 = next()?; let b1 = next()?; let b2 = next()?; let b3 = next()?;
+        if (8..0x8000).contains(&w) && b0 == 2 && b1 == 2 && ((b2 as u32) << 8 | b3 as u32) == w {
+            // RLE moderno: cada canal por separado
+            for ch in 0..4 {
+                let mut x = 0usize;
+                while x < w as usize {
+                    let count = next()?;
+                    if count > 128 {
+                        let run = (count - 128) as usize;
+                        let v = next()?;
+                        if x + run > w as usize { return Err(format!("{}: bad RLE run", path)); }
+                        for px in &mut scan[x..x + run] { px[ch] = v; }
+                        x += run;
+                    } else {
+                        let run = count as usize;
+                        if run == 0 || x + run > w as usize { return Err(format!("{}: bad RLE run", path)); }
+                        for px in &mut scan[x..x + run] { px[ch] = next()?; }
+                        x += run;
+                    }
+                }
+            }
+        } else {
+            // sin compresión
+            scan[0] = [b0, b1, b2, b3];
+            for px in scan.iter_mut().skip(1) { *px = [next()?, next()?, next()?, next()?]; }
+        }
+        for px in &scan {
+            if px[3] == 0 { data.push(Color::black()); continue; }
+            let f = 2f32.powi(px[3] as i32 - 136);
+            data.push(Color::new((px[0] as f32 + 0.5) * f, (px[1] as f32 + 0.5) * f, (px[2] as f32 + 0.5) * f));
+        }
+    }
+    eprintln!("[hdr] loaded {} ({}x{})", path, w, h);
+    Ok(LoadedTexture { w, h, data, anim: None })
+}
+
+// Escritor Radiance sin RLE (el lector de arriba lo acepta). RGBE no guarda negativos: se recortan a 0.
+pub fn write_hdr(path: &str, width: u32, height: u32, pixels: &[Color]) -> Result<(), String> {
+    let mut out = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", height, width).into_bytes();
+    for c in pixels {
+        let (r, g, b) = (c.r.max(0.0), c.g.max(0.0), c.b.max(0.0));
+        let v = r.max(g).max(b);
+        if v < 1e-32 { out.extend_from_slice(&[0, 0, 0, 0]); continue; }
+        let e = v.log2().floor() as i32 + 1;
+        let scale = 256.0 / 2f32.powi(e);
+        out.extend_from_slice(&[(r * scale).min(255.0) as u8, (g * scale).min(255.0) as u8, (b * scale).min(255.0) as u8, (e + 128).clamp(0, 255) as u8]);
+    }
+    std::fs::write(path, out).map_err(|e| format!("{}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_bytes(name: &str, bytes: &[u8]) -> Result<LoadedTexture, String> {
+        let path = std::env::temp_dir().join(format!("proyecto2-{}-{}.hdr", name, std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        let tex = read_hdr(path.to_str().unwrap());
+        let _ = std::fs::remove_file(&path);
+        tex
+    }
+
+    #[test]
+    fn reads_run_length_encoded_scanlines() {
+        let mut file = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 8\n".to_vec();
+        file.extend_from_slice(&[2, 2, 0, 8]);
+        // R: una corrida de 8; G: 8 valores sueltos; B: dos corridas de 4; E: 128 en todos (escala 1/256)
+        file.extend_from_slice(&[128 + 8, 64]);
+        file.extend_from_slice(&[8, 0, 32, 64, 96, 128, 160, 192, 224]);
+        file.extend_from_slice(&[128 + 4, 0, 128 + 4, 255]);
+        file.extend_from_slice(&[128 + 8, 128]);
+        let tex = read_bytes("rle", &file).unwrap();
+        assert_eq!((tex.w, tex.h), (8, 1));
+        let c = tex.data[5];
+        assert_eq!((c.r, c.g, c.b), (64.5 / 256.0, 160.5 / 256.0, 255.5 / 256.0));
+        assert_eq!(tex.data[0].b, 0.5 / 256.0);
+    }
+
+    #[test]
+    fn rejects_other_files_and_truncated_data() {
+        assert!(read_bytes("png", b"\x89PNG\r\n").is_err());
+        assert!(read_bytes("flip", b"#?RADIANCE\n\n+Y 1 +X 1\n\x80\x80\x80\x80").is_err());
+        assert!(read_bytes("short", b"#?RADIANCE\n\n-Y 2 +X 1\n\x80\x80\x80\x81").is_err());
+    }
+
+    #[test]
+    fn written_files_read_back_within_rgbe_precision() {
+        let path = std::env::temp_dir().join(format!("proyecto2-write-{}.hdr", std::process::id()));
+        let pixels = [Color::new(0.5, 0.25, 1.0), Color::new(1234.5, 0.001, 7.0), Color::black(), Color::new(-1.0, 2.0, 0.3)];
+        write_hdr(path.to_str().unwrap(), 2, 2, &pixels).unwrap();
+        let tex = read_hdr(path.to_str().unwrap());
+        let _ = std::fs::remove_file(&path);
+        let tex = tex.unwrap();
+        assert_eq!((tex.w, tex.h), (2, 2));
+        for (a, b) in pixels.iter().zip(&tex.data) {
+            // la mantisa de 8 bits es relativa al canal más brillante; los negativos quedan en 0
+            let tol = a.r.max(a.g).max(a.b) / 128.0;
+            for (x, y) in [(a.r.max(0.0), b.r), (a.g.max(0.0), b.g), (a.b.max(0.0), b.b)] {
+                assert!((x - y).abs() <= tol, "{:?} -> {:?}", a, b);
+            }
+        }
+        assert_eq!((tex.data[2].r, tex.data[2].g, tex.data[2].b), (0.0, 0.0, 0.0));
+    }
+}
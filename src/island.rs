@@ -142,3 +142,13 @@ pub fn build_island(world: &mut VoxelWorld, surface_mat: Material, trunk: Materi
     }
 }
 
+
+// Capa de nieve: un vóxel encima de cada tierra u hoja que mira al cielo (no sobre agua ni piedra).
+pub fn cover_with_snow(world: &mut VoxelWorld, snow: Material) {
+    for ((x, y, z), m) in world.exposed_voxels() {
+        if matches!(m.kind, MaterialKind::Terrain | MaterialKind::Leaves | MaterialKind::Trunk) {
+            world.add_voxel(x, y + 1, z, snow);
+        }
+    }
+    world.recompute_exposed();
+}
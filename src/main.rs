@@ -10,20 +10,57 @@ mod voxel_world;
 mod island;
 mod skybox;
 mod framebuffer;
+mod config;
+mod hdr;
+mod envmap;
+mod sampling;
+mod daycycle;
+mod clouds;
+mod fog;
+mod water;
+mod caustics;
+mod subsurface;
+mod animation;
+mod tonemap;
+mod aov;
+mod exr;
+mod denoise;
+mod postfx;
+mod dynres;
+mod temporal;
+mod tiles;
+mod distributed;
 
-use camera::OrbitCamera;
+use camera::{Camera, FixedCamera, FlyCamera, Lens, LookAtCamera, OrbitCamera, Projection};
+use animation::CameraAnimation;
+use tonemap::ToneMap;
+use aov::AovSample;
+use denoise::Denoiser;
+use postfx::PostStack;
+use dynres::DynamicResolution;
+use temporal::TemporalFilter;
+use tiles::{CancelToken, Progress, TileSettings};
+use distributed::Job;
 use color::Color;
 use light::PointLight;
 use material::{Material, MaterialKind};
 use ray_intersect::{Ray, SceneObject};
 
-use texture::{Textures, sample_grass_from_textures, sample_trunk_from_textures, sample_leaves_from_textures, sample_water_from_textures, sample_stone_from_textures, LoadedTexture, load_png};
+use texture::{Textures, sample_grass_from_textures, sample_trunk_from_textures, sample_leaves_from_textures, sample_water_from_textures, sample_stone_from_textures, LoadedTexture, load_texture};
 use voxel_world::VoxelWorld;
-use island::{build_island, IslandParams};
+use island::{build_island, cover_with_snow, IslandParams};
 
 
 use skybox::Skybox;
-use framebuffer::RLFramebuffer;
+use framebuffer::{RLFramebuffer, save_png};
+use config::Config;
+use envmap::EnvMap;
+use sampling::Sampler;
+use daycycle::DayCycle;
+use clouds::{CloudLayer, CloudStyle};
+use fog::Fog;
+use water::{WaterMedium, Waves};
+use caustics::CausticMap;
 use rand::prelude::*; 
 use rayon::prelude::*;
 
@@ -33,63 +70,121 @@ const RENDER_SCALE: f32 = 1.0;
 const MAX_DEPTH: i32 = 4;
 
 
-fn trace(ray: Ray, world: &VoxelWorld, depth: i32, sun_dir: color::Vec3, sky: &Skybox, tex: &Textures, chest_front: &LoadedTexture, chest_side: &LoadedTexture) -> Color {
+// Todo lo que `trace` necesita de la escena para un cuadro.
+#[derive(Clone, Copy)]
+struct Scene<'a> {
+    world: &'a VoxelWorld,
+    sky: &'a Skybox,
+    tex: &'a Textures,
+    chest_front: &'a LoadedTexture,
+    chest_side: &'a LoadedTexture,
+    clouds: &'a CloudLayer,
+    fog: &'a Fog,
+    water: &'a WaterMedium,
+    caustics: &'a CausticMap,
+    time: f32, // segundos, mueve texturas animadas y ondas del agua
+    env_samples: u32, // muestras de luz ambiente con panorama HDR (0 = sin sombras, vía SH)
+    sss_samples: u32, // caminatas por píxel en materiales con dispersión subsuperficial
+    max_depth: i32,   // rebotes de los rayos primarios (baja con la resolución dinámica)
+    seed: u64,        // semilla global de las secuencias aleatorias de cada píxel
+}
+
+// `rng` es la secuencia de la muestra del píxel que originó el rayo; la siguen sus rebotes.
+fn trace(ray: Ray, scene: &Scene, depth: i32, rng: &mut Sampler) -> Color {
+    trace_with(ray, scene, depth, rng, None)
+}
+
+// Como `trace`, pero anota en `aov` lo que encontró el rayo (solo se usa en rayos primarios).
+fn trace_with(ray: Ray, scene: &Scene, depth: i32, rng: &mut Sampler, mut aov: Option<&mut AovSample>) -> Color {
     if depth <= 0 { return Color::black(); }
-    let mut closest: Option<ray_intersect::HitInfo> = None;
-    if let Some(h) = world.intersect(&ray) { closest = Some(h); }
+    let in_water = scene.world.is_water(ray.origin);
+    let closest = if in_water { scene.world.intersect_from_water(&ray) } else { scene.world.intersect(&ray) };
+    let t_max = closest.as_ref().map_or(f32::INFINITY, |h| h.t);
+    let mut col = shade(ray, closest, scene, depth, rng, aov.as_deref_mut());
+    let light = scene.sky.key_light();
+    let up = color::Vec3::new(0.0, 1.0, 0.0);
+    if in_water {
+        // bajo el agua no hay nubes ni niebla de aire, solo el propio medio
+        if let Some(a) = aov { a.direct = a.direct * scene.water.transmittance(t_max.min(1e4)); }
+        let lit = scene.sky.ambient(up) + light.1 * light.0.y.max(0.0);
+        return scene.water.apply(col, t_max, lit);
+    }
+    // la luz directa pasa por las mismas nubes y niebla que el color, así directa + indirecta = final
+    let mut through = 1.0;
+    if scene.clouds.style != CloudStyle::Off {
+        let t;
+        (col, t) = scene.clouds.composite(&ray, t_max, col, light, scene.sky.ambient(up), scene.time);
+        through *= t;
+    }
+    if let Some(a) = aov { a.direct = a.direct * (through * scene.fog.transmittance(&ray, t_max)); }
+    // los haces de luz solo en rayos primarios: en reflejos casi no se notan y cuestan mucho
+    scene.fog.apply(&ray, t_max, col, scene, depth == scene.max_depth)
+}
+
+// Color de lo que ve el rayo (superficie o cielo), sin las nubes que haya delante.
+fn shade(ray: Ray, closest: Option<ray_intersect::HitInfo>, scene: &Scene, depth: i32, rng: &mut Sampler, mut aov: Option<&mut AovSample>) -> Color {
+    let Scene { world, sky, tex, chest_front, chest_side, clouds, fog: _, water, caustics, time, env_samples, sss_samples, max_depth: _, seed: _ } = *scene;
     if let Some(hit) = closest {
-     
-        if hit.material.kind == MaterialKind::Cloud {
-            return Color::white();
-        }
         const EPS: f32 = 4e-4;
-        let light_dir = -sun_dir;
+        let (light_dir, light_col) = sky.key_light();
+        let light_col = light_col * clouds.transmittance(hit.position, light_dir, time);
+        // el fondo del estanque recibe la luz atenuada por el agua que tiene encima
+        let water_depth = if hit.material.kind == MaterialKind::Water { 0.0 } else { world.water_depth(hit.position) };
+        // con cáusticas, la luz bajo el agua la traen los fotones (ya atenuados) y no el sol directo
+        let caustic_col = if caustics.enabled() { light_col } else { Color::black() };
+        let light_col = if caustics.enabled() && water_depth > 0.0 { Color::black() } else { light_col * water.light_at_depth(water_depth, light_dir) };
         let shadow_origin = hit.position + hit.normal * (EPS * 6.0) + light_dir * (EPS * 4.0);
-        let in_shadow = if hit.material.kind == MaterialKind::Water {
+        let in_shadow = if hit.material.kind == MaterialKind::Water || water_depth > 0.0 {
             world.occluded_ignore_water(shadow_origin, light_dir, 200.0)
         } else {
             world.occluded(shadow_origin, light_dir, 200.0)
         };
         let view_dir = (-ray.dir).normalized();
-        let ambient = 0.05;
-        let ndotl = hit.normal.dot(light_dir).max(0.0);
-        let sun_intensity = 1.4;
-        let (diffuse_f, specular_f) = if !in_shadow && ndotl > 0.0 {
-            let reflect_dir = (2.0 * hit.normal * ndotl - light_dir).normalized();
-            let spec_angle = reflect_dir.dot(view_dir).max(0.0);
-            (ndotl * sun_intensity, spec_angle.powf(hit.material.shininess) * hit.material.specular * sun_intensity)
-        } else { (0.0, 0.0) };
+        if let Some(a) = aov.as_deref_mut() {
+            let v = hit.position - hit.normal * 0.5;
+            a.depth = hit.t;
+            a.position = hit.position;
+            a.uv = (hit.u, hit.v);
+            a.material = hit.material.kind as u32 as f32 + 1.0;
+            a.voxel = color::Vec3::new(v.x.round(), v.y.round(), v.z.round());
+            a.shadow = if in_shadow { 1.0 } else { 0.0 };
+        }
         let mut base_col = hit.material.color;
         let mut is_water = false;
         let mut water_normal = hit.normal;
     if hit.material.kind == MaterialKind::Water {
             is_water = true;
        
-            let p = hit.position * 3.3;
-            let hx = ((p.x.floor() as i32).wrapping_mul(92837111) ^ (p.z.floor() as i32).wrapping_mul(689287499)) as u32;
-            let hy = ((p.x.floor() as i32 + 13).wrapping_mul(362437) ^ (p.z.floor() as i32 + 17).wrapping_mul(97531)) as u32;
-            let n1 = ((hx ^ (hx>>13)) & 0xffff) as f32 / 65535.0;
-            let n2 = ((hy ^ (hy>>11)) & 0xffff) as f32 / 65535.0;
-            let ang = n1 * std::f32::consts::TAU;
-            let amp = 0.05 + 0.05 * n2;
-            let t1 = if hit.normal.y.abs() < 0.9 { color::Vec3::new(0.0,1.0,0.0).cross(hit.normal).normalized() } else { color::Vec3::new(1.0,0.0,0.0) };
-            let t2 = hit.normal.cross(t1).normalized();
-            let ripple = (t1 * ang.cos() + t2 * ang.sin()) * amp;
-            water_normal = (hit.normal + ripple).normalized();
+            // normal suave de las olas, continua entre vóxeles
+            if hit.normal.y > 0.5 { water_normal = world.water_normal(hit.position); }
        
-            let tex_col = sample_water_from_textures(hit.u, hit.v, tex);
+            let tex_col = sample_water_from_textures(hit.u, hit.v, tex, time);
             let up_factor = hit.normal.y.max(0.0);
-            base_col = (tex_col * (0.6 + 0.3*up_factor) + base_col * 0.3).clamped();
+            base_col = tex_col * (0.6 + 0.3*up_factor) + base_col * 0.3;
         }
+    let mut shade_n = hit.normal;
     if hit.object_id == ray_intersect::ObjectId::Cube && hit.material.kind != MaterialKind::Glass {
         let center_pos = hit.position - hit.normal * 0.5;
         let vx = center_pos.x.round() as i32; let vy = center_pos.y.round() as i32; let vz = center_pos.z.round() as i32;
+        const CHEST_POS: (i32,i32,i32) = (-1, 7, 1); // (x,y,z)
+        let exposed = world.is_top_exposed(vx, vy, vz);
+        // relieve (normal map + parallax) en corteza, piedra y tierra
+        let relief = match hit.material.kind {
+            MaterialKind::Trunk => tex.trunk_relief.as_ref(),
+            MaterialKind::Stone if (vx, vy, vz) != CHEST_POS => tex.stone_relief.as_ref(),
+            MaterialKind::Terrain if !exposed || hit.normal.y < -0.5 => tex.dirt_relief.as_ref(),
+            _ => None,
+        };
+        let (mut u, mut v) = (hit.u, hit.v);
+        if let Some(r) = relief {
+            (u, v) = r.parallax(u, v, hit.tangent, hit.bitangent, hit.normal, view_dir);
+            shade_n = r.perturb(u, v, hit.tangent, hit.bitangent, hit.normal);
+        }
         match hit.material.kind {
-            MaterialKind::Terrain => { let exposed = world.is_top_exposed(vx, vy, vz); base_col = sample_grass_from_textures(hit.normal, hit.u, hit.v, tex, exposed); },
-            MaterialKind::Trunk => { base_col = sample_trunk_from_textures(hit.normal, hit.u, hit.v, tex); },
-            MaterialKind::Leaves => { base_col = sample_leaves_from_textures(hit.u, hit.v, tex); },
+            MaterialKind::Terrain => { base_col = sample_grass_from_textures(hit.normal, u, v, tex, exposed, time); },
+            MaterialKind::Trunk => { base_col = sample_trunk_from_textures(hit.normal, u, v, tex, time); },
+            MaterialKind::Leaves => { base_col = sample_leaves_from_textures(u, v, tex, time); },
             MaterialKind::Stone => {
-                const CHEST_POS: (i32,i32,i32) = (-1, 7, 1); // (x,y,z)
                 if (vx, vy, vz) == CHEST_POS {
                     let n = hit.normal;
                     
@@ -101,17 +196,44 @@ fn trace(ray: Ray, world: &VoxelWorld, depth: i32, sun_dir: color::Vec3, sky: &S
                     if n.z > 0.5 { u = 1.0 - u; } 
                 
                     if n.z < -0.5 { 
-                        base_col = chest_front.sample(u, v);
+                        base_col = chest_front.sample_at(u, v, time);
                     } else {
-                        base_col = chest_side.sample(u, v);
+                        base_col = chest_side.sample_at(u, v, time);
                     }
                 } else {
-                    base_col = sample_stone_from_textures(hit.u, hit.v, tex);
+                    base_col = sample_stone_from_textures(u, v, tex, time);
                 }
             },
             _ => {}
         }
     }
+        // luz ambiente: con panorama y muestras > 0 se integra con rayos de sombra (Monte Carlo,
+        // muestreo por importancia del panorama); si no, la aproximación sin sombras del cielo
+        let ambient = match sky.environment() {
+            Some(env) if env_samples > 0 => {
+                let origin = hit.position + hit.normal * (EPS * 6.0);
+                let mut acc = Color::black();
+                for _ in 0..env_samples {
+                    let (dir, radiance, pdf) = env.sample(rng.next_f32(), rng.next_f32());
+                    let cos = shade_n.dot(dir);
+                    if cos <= 0.0 || pdf <= 0.0 || hit.normal.dot(dir) <= 0.0 { continue; }
+                    if world.occluded(origin, dir, 200.0) { continue; }
+                    acc = acc + radiance * (cos / (pdf * std::f32::consts::PI));
+                }
+                acc * (1.0 / env_samples as f32)
+            }
+            _ => sky.ambient(shade_n),
+        };
+        let ndotl = shade_n.dot(light_dir).max(0.0);
+        let sun_intensity = 1.4;
+        let (diffuse_f, specular_f) = if !in_shadow && ndotl > 0.0 {
+            let reflect_dir = (2.0 * shade_n * ndotl - light_dir).normalized();
+            let spec_angle = reflect_dir.dot(view_dir).max(0.0);
+            (ndotl * sun_intensity, spec_angle.powf(hit.material.shininess) * hit.material.specular * sun_intensity)
+        } else { (0.0, 0.0) };
+        // antes de la interfaz del agua vista desde abajo, que vuelve sin pasar por el resto
+        // (toda su luz llega por la interfaz: la directa queda en cero)
+        if let Some(a) = aov.as_deref_mut() { a.albedo = base_col; a.normal = shade_n; }
         // Reflexión / Refracción
     let mut refl_col = Color::black();
     let mut refr_col = Color::black();
@@ -119,9 +241,13 @@ fn trace(ray: Ray, world: &VoxelWorld, depth: i32, sun_dir: color::Vec3, sky: &S
         if hit.material.reflectivity > 0.01 {
             let rdir = (ray.dir - n * 2.0 * ray.dir.dot(n)).normalized();
             let r_origin = hit.position + rdir * EPS * 6.0;
-            refl_col = trace(Ray { origin: r_origin, dir: rdir }, world, depth - 1, sun_dir, sky, tex, chest_front, chest_side);
+            refl_col = trace(Ray { origin: r_origin, dir: rdir }, scene, depth - 1, rng);
         }
-        if hit.material.transparency > 0.01 {
+        if is_water {
+            refr_col = water_interface(ray, &hit, n, scene, depth, rng);
+            // desde abajo solo se ve la interfaz: la ventana de Snell o la reflexión total
+            if ray.dir.dot(hit.normal) > 0.0 { return refr_col; }
+        } else if hit.material.transparency > 0.01 {
             let mut n1 = 1.0; let mut n2 = hit.material.ior;
             let mut normal = n;
             let cos_i = -normal.dot(ray.dir).max(-1.0).min(1.0);
@@ -133,100 +259,180 @@ fn trace(ray: Ray, world: &VoxelWorld, depth: i32, sun_dir: color::Vec3, sky: &S
             if k >= 0.0 {
                 let refr_dir = (ray.dir * eta + normal * (eta * cos_i - k.sqrt())).normalized();
                 let r_origin = hit.position + refr_dir * EPS * 4.0;
-                if is_water {
-             
-                    let mut current_origin = r_origin;
-                    let mut steps = 0;
-                    let max_steps = 16;
-                    let mut final_col = Color::black();
-                    let mut hit_solid = false;
-                    loop {
-                        if steps >= max_steps { break; }
-                        if let Some(h2) = world.intersect(&Ray{origin: current_origin, dir: refr_dir}) {
-                            if h2.material.kind == MaterialKind::Water {
-                                current_origin = h2.position + refr_dir * EPS * 8.0; 
-                                continue;
-                            } else {
-                     
-                                current_origin = h2.position + refr_dir * EPS * 2.0;
-                                final_col = trace(Ray{origin: current_origin, dir: refr_dir}, world, depth - 1, sun_dir, sky, tex, chest_front, chest_side);
-                                hit_solid = true;
-                                break;
-                            }
-                        } else {
-           
-                            let tsky = 0.5 * (refr_dir.y + 1.0);
-                            final_col = Color::new(0.2,0.3,0.6)*(1.0 - tsky) + Color::new(0.8,0.9,1.0)*tsky;
-                            break;
-                        }
-                    }
-          
-                    if !hit_solid {
-                        let down = color::Vec3::new(0.0, -1.0, 0.0);
-                        if let Some(_h3) = world.intersect(&Ray{ origin: current_origin, dir: down }) {
-                            final_col = trace(Ray{ origin: current_origin, dir: down }, world, depth - 1, sun_dir, sky, tex, chest_front, chest_side);
-                        }
-                    }
-                    let depth_factor = (steps as f32 * 0.16).min(1.0);
-                    let absorption = Color::new(0.02,0.04,0.08) * depth_factor * 0.7;
-                    refr_col = (final_col * (1.0 - 0.45*depth_factor) + absorption).clamped();
-                } else {
-                    refr_col = trace(Ray { origin: r_origin, dir: refr_dir }, world, depth - 1, sun_dir, sky, tex, chest_front, chest_side);
-                }
+                refr_col = trace(Ray { origin: r_origin, dir: refr_dir }, scene, depth - 1, rng);
             }
             if hit.material.reflectivity < 0.01 {
                     let r0 = ((n1 - n2) / (n1 + n2)).powi(2);
                 let c = 1.0 - cos_i.abs();
                 let fresnel = r0 + (1.0 - r0) * c.powi(5);
-
-                if is_water {
-                    let rdir = (ray.dir - n * 2.0 * ray.dir.dot(n)).normalized();
-                    let r_origin = hit.position + rdir * EPS * 6.0;
-                    let surface_ref = trace(Ray { origin: r_origin, dir: rdir }, world, depth - 1, sun_dir, sky, tex, chest_front, chest_side);
-                    refr_col = surface_ref * fresnel + refr_col * (1.0 - fresnel);
-                    refr_col = surface_ref * fresnel + refr_col * (1.0 - fresnel);
-                } else {
-                    refl_col = refr_col * fresnel + refl_col * (1.0 - fresnel);
-                }
+                refl_col = refr_col * fresnel + refl_col * (1.0 - fresnel);
             }
         }
         let base = base_col * ambient;
-        let diff_col = base_col * diffuse_f;
-        let spec_col = Color::white() * specular_f;
+        let mut diff_col = base_col * light_col * diffuse_f;
+        let m = hit.material;
+        if m.mean_free_path > 0.0 && sss_samples > 0 {
+            // la luz entra por donde puede y sale aquí, ya filtrada por el material
+            let walk = subsurface::random_walk(world, &m, hit.position, hit.normal, light_dir, sss_samples, rng);
+            diff_col = base_col * light_col * walk * sun_intensity;
+        }
+        if m.translucency > 0.0 {
+            diff_col = diff_col + base_col * light_col * subsurface::translucency(world, &m, shadow_origin, shade_n, view_dir, light_dir) * sun_intensity;
+        }
+        if caustics.enabled() && hit.material.kind != MaterialKind::Water && hit.material.transparency <= 0.01 {
+            diff_col = diff_col + base_col * caustic_col * caustics.gather(hit.position, shade_n) * sun_intensity;
+        }
+        let mut spec_col = light_col * specular_f;
+        if hit.material.reflectivity <= 0.01 && hit.material.transparency <= 0.01 {
+            let rdir = (ray.dir - shade_n * 2.0 * ray.dir.dot(shade_n)).normalized();
+            if let Some(env_spec) = sky.env_specular(rdir, hit.material.shininess) { spec_col = spec_col + env_spec * hit.material.specular; }
+        }
+        if let Some(a) = aov { a.direct = (diff_col + spec_col) * (1.0 - hit.material.transparency) * (1.0 - hit.material.reflectivity); }
         let mut surf = base + diff_col + spec_col;
         if hit.material.transparency > 0.0 { surf = surf * (1.0 - hit.material.transparency) + refr_col * hit.material.transparency; }
         if hit.material.reflectivity > 0.0 { surf = surf * (1.0 - hit.material.reflectivity) + refl_col * hit.material.reflectivity; }
-        surf.clamped()
+        surf
     } else {
 
     sky.sample(ray.dir)
     }
 }
 
+// Interfaz aire/agua: mezcla reflexión y refracción con Fresnel. Desde abajo, fuera del cono
+// de ~48.6° alrededor de la vertical (la ventana de Snell) hay reflexión total interna.
+fn water_interface(ray: Ray, hit: &ray_intersect::HitInfo, n: color::Vec3, scene: &Scene, depth: i32, rng: &mut Sampler) -> Color {
+    const EPS: f32 = 4e-4;
+    let ior = hit.material.ior;
+    let from_below = ray.dir.dot(hit.normal) > 0.0;
+    let (n, eta, side) = if from_below { (-n, ior, -hit.normal) } else { (n, 1.0 / ior, hit.normal) };
+    let cos_i = (-ray.dir.dot(n)).clamp(0.0, 1.0);
+    let rdir = (ray.dir + n * (2.0 * cos_i)).normalized();
+    let refl = trace(Ray { origin: hit.position + side * (EPS * 2.0) + rdir * (EPS * 4.0), dir: rdir }, scene, depth - 1, rng);
+    let k = 1.0 - eta * eta * (1.0 - cos_i * cos_i);
+    if k < 0.0 { return refl; }
+    let cos_t = k.sqrt();
+    let tdir = (ray.dir * eta + n * (eta * cos_i - cos_t)).normalized();
+    let refr = trace(Ray { origin: hit.position - side * (EPS * 2.0) + tdir * (EPS * 4.0), dir: tdir }, scene, depth - 1, rng);
+    let r0 = ((ior - 1.0) / (ior + 1.0)).powi(2);
+    let c = 1.0 - if from_below { cos_t } else { cos_i };
+    let fresnel = r0 + (1.0 - r0) * c.powi(5);
+    refl * fresnel + refr * (1.0 - fresnel)
+}
+
+// Sol, luna y cáusticas para la hora actual del ciclo.
+fn update_lighting(skybox: &mut Skybox, cycle: &DayCycle, caustics: &mut CausticMap, world: &VoxelWorld, water: &WaterMedium, seed: u64) {
+    skybox.set_night(cycle.moon_direction(), cycle.moon_light(), cycle.moon_illumination(), cycle.latitude.to_radians(), cycle.sidereal_angle());
+    skybox.set_sun(cycle.sun_direction());
+    caustics.update(world, water, skybox.key_light().0, seed);
+}
+
+// Tamaño de los renders sin ventana: `--height` es el alto de un ojo o de una cara del cubemap.
+fn output_size(config: &Config, lens: &Lens) -> (u32, u32) {
+    let height = config.get_u32("height", match lens.projection { Projection::Equirectangular => 1024, Projection::Cubemap => 512, _ => HEIGHT as u32 });
+    let width = config.get_str("width").and_then(|w| w.parse().ok());
+    lens.image_size(width, height.max(1))
+}
+
+// Traza una imagen completa de `width`×`height`, con varios rayos por píxel si la lente desenfoca.
+// Con `want_aovs` también devuelve los pases del primer rayo de cada píxel; si no, queda vacío.
+// `jitter` corre los rayos dentro del píxel (en fracciones de píxel) para el antialiasing temporal.
+fn render(scene: &Scene, view: &dyn Camera, lens: &Lens, width: u32, height: u32, jitter: (f32, f32), want_aovs: bool) -> (Vec<Color>, Vec<AovSample>) {
+    let pixel = pixel_tracer(scene, view, lens, width, height, jitter, want_aovs);
+    let n = (width * height) as usize;
+    if want_aovs { return (0..n).into_par_iter().map(pixel).unzip(); }
+    ((0..n).into_par_iter().map(|i| pixel(i).0).collect(), Vec::new())
+}
+
+// Color (y pases, si se piden) del píxel `i` de una imagen de `width`×`height`.
+fn pixel_tracer<'a>(scene: &'a Scene, view: &'a dyn Camera, lens: &'a Lens, width: u32, height: u32, jitter: (f32, f32), want_aovs: bool) -> impl Fn(usize) -> (Color, AovSample) + Sync + 'a {
+    let aspect = width as f32 / height as f32;
+    let rays = lens.rays_per_pixel();
+    move |i: usize| {
+        let x = (i as u32) % width;
+        let y = (i as u32) / width;
+        // centro del píxel, así las costuras de los panoramas no repiten columnas
+        let u = (x as f32 + 0.5 + jitter.0) / width as f32;
+        let v = (y as f32 + 0.5 + jitter.1) / height as f32;
+        let mut col = Color::black();
+        let mut aov = AovSample::default();
+        let mut direct = Color::black();
+        for k in 0..rays {
+            // cada rayo del píxel con su propia secuencia: el resultado no depende del hilo que lo traza
+            let mut rng = Sampler::stream(scene.seed, i as u64, k as u64);
+            let ray = view.generate_ray(lens, u, v, aspect, (rng.next_f32(), rng.next_f32()));
+            let mut sample = AovSample::default();
+            col = col + trace_with(ray, scene, scene.max_depth, &mut rng, if want_aovs { Some(&mut sample) } else { None });
+            // los pases son los del primer rayo, salvo la luz directa, que se promedia como el color
+            if k == 0 { aov = sample; }
+            direct = direct + sample.direct;
+        }
+        aov.direct = direct * (1.0 / rays as f32);
+        (col * (1.0 / rays as f32), aov)
+    }
+}
+
+// Guarda un render terminado: EXR y HDR con la radiancia lineal tal cual; un PNG pasa por el
+// mapeo de tonos y el postproceso y, si se pidieron pases, los escribe además en .hdr junto a él.
+fn save_image(path: &str, (w, h): (u32, u32), pixels: &[Color], aovs: &[AovSample], passes: &[aov::Pass], tonemap: &mut ToneMap, post: &PostStack) -> Result<(), String> {
+    let lower = path.to_ascii_lowercase();
+    if lower.ends_with(".exr") || lower.ends_with(".hdr") {
+        return aov::write_float_outputs(path, w, h, pixels, aovs, passes);
+    }
+    tonemap.meter(pixels, f32::INFINITY);
+    save_png(path, w, h, &post.apply(tonemap.apply(pixels), pixels, tonemap.scale(), w, h, 0))?;
+    if passes.is_empty() { return Ok(()); }
+    let stem = path.rsplit_once('.').map_or(path, |(stem, _)| stem);
+    aov::write_float_outputs(&format!("{}.hdr", stem), w, h, pixels, aovs, passes)
+}
+
+// Trabajo de render distribuido: el bloque `rect` de una imagen de `w`×`h`, con la vista y la lente de aquí.
+fn remote_job(id: u32, (w, h): (u32, u32), rect: (u32, u32, u32, u32), time: f32, hours: f32, seed: u64, view: &dyn Camera, lens: &Lens) -> Job {
+    Job { id, width: w, height: h, rect, time, hours, seed, fov: lens.fov, focus_distance: lens.focus_distance, position: view.position(), basis: view.basis() }
+}
+
+// `render.png` -> `render.partial.png`, para los renders cancelados.
+fn partial_path(path: &str) -> String {
+    match path.rsplit_once('.') { Some((stem, ext)) => format!("{}.partial.{}", stem, ext), None => format!("{}.partial", path) }
+}
+
 fn main() {
-    let skybox = Skybox::new();
+    let mut config = Config::from_args();
+    // trabajador de render distribuido: las opciones y los archivos de la escena llegan del coordinador
+    let mut worker = None;
+    if let Some(addr) = config.get_str("worker").map(str::to_string) {
+        match distributed::Worker::connect(&addr) {
+            Ok((w, cfg)) => { worker = Some(w); config = cfg; }
+            Err(err) => { eprintln!("[worker] {}", err); return; }
+        }
+    }
+    let mut skybox = Skybox::new();
+    if let Some(path) = config.get_str("env") {
+        match EnvMap::load(path, config.get_f32("env_rotation", 0.0), config.get_f32("env_intensity", 1.0)) {
+            Ok(env) => skybox.set_environment(env, config.get_bool("env_sun", true)),
+            Err(err) => eprintln!("[env] {}", err),
+        }
+    }
+    let env_samples = config.get_u32("env_samples", 0);
+    let sss_samples = config.get_u32("sss_samples", 8);
     
-    let textures = Textures::load_folder("textures");
-    let chest_front_tex = load_png("textures/cofre-frontal.png");
-    let chest_side_tex = load_png("textures/cofre-lado.png");
+    let textures = Textures::load_folder("textures", &config);
+    let chest_front_tex = load_texture("textures/cofre-frontal.png");
+    let chest_side_tex = load_texture("textures/cofre-lado.png");
 
 
-    let (mut rl, thread) = raylib::init()
-        .size(WIDTH as i32, HEIGHT as i32)
-        .title("Raytracer 3D - Proyecto2 (raylib)")
-        .build();
-    rl.set_target_fps(60);
     let dirt_grass_mat = Material::new_basic(Color::new(0.4, 0.3, 0.2), 0.35, 24.0, MaterialKind::Terrain);
     let stone_mat = Material::new_stone(Color::new(0.5,0.5,0.52));
     let water_mat = Material::new_water(Color::new(0.25,0.4,0.55));
     let trunk_mat = Material::new_basic(Color::new(0.45, 0.28, 0.12), 0.2, 12.0, MaterialKind::Trunk);
-    let leaves_mat = Material::new_basic(Color::new(0.18, 0.55, 0.22), 0.08, 8.0, MaterialKind::Leaves);
+    let leaves_mat = Material::new_leaves(Color::new(0.18, 0.55, 0.22));
+    let snow_mat = Material::new_snow(Color::new(0.92, 0.94, 0.97));
     let _glass_mat = Material::new_glass(Color::new(0.9, 0.95, 1.0), 1.52, 0.15, 0.9);
 
    
     let mut world = VoxelWorld::new();
     let params = IslandParams { top_radius: 7, top_height: 6, plateau_variation: 0, depth: 8 };
     let top_height = params.top_height; 
+    let island_bottom = (params.top_height - params.depth) as f32;
     build_island(&mut world, dirt_grass_mat, trunk_mat, leaves_mat, stone_mat, params);
     world.recompute_exposed();
 
@@ -277,53 +483,340 @@ fn main() {
     world.recompute_exposed();
 
     world.enforce_water_border(dirt_grass_mat);
-    let internal_w = (WIDTH as f32 * RENDER_SCALE) as u32;
-    let internal_h = (HEIGHT as f32 * RENDER_SCALE) as u32;
-    let mut fb = RLFramebuffer::new(internal_w, internal_h);
+    let clouds = CloudLayer::from_config(&config, world.take_clouds());
+    if config.get_bool("snow", false) { cover_with_snow(&mut world, snow_mat); }
+    let fog = Fog::from_config(&config, island_bottom);
+    let water = WaterMedium::from_config(&config);
+    world.set_waves(Waves::from_config(&config));
+    let mut caustics = CausticMap::from_config(&config);
 
-
-    let mut rng = rand::rng();
-    let mut sun_az: f32 = rng.random_range(-1.2_f32..1.2_f32);  
-    let mut sun_el: f32 = rng.random_range(0.6_f32..1.2_f32);  
+    // todo lo aleatorio sale de la semilla: con `--seed` dos corridas dan la misma imagen
+    let seed = match config.get_str("seed") {
+        Some(_) => config.get_u64("seed", 0),
+        None => { let seed = rand::rng().random(); eprintln!("[seed] {} (repeat with --seed {})", seed, seed); seed }
+    };
+    let mut rng = Sampler::new(seed);
+    let mut cycle = DayCycle::from_config(&config, 8.0 + 8.0 * rng.next_f32());
   
-    if rng.random_bool(0.5) { camera.orbit_delta(rng.random_range(-0.3..0.3), rng.random_range(-0.1..0.1)); }
+    if rng.next_f32() < 0.5 { camera.orbit_delta(rng.next_f32() * 0.6 - 0.3, rng.next_f32() * 0.2 - 0.1); }
+    let mut lens = Lens::from_config(&config);
+    let mut tonemap = ToneMap::from_config(&config);
+    let mut denoiser = Denoiser::from_config(&config);
+    let post = PostStack::from_config(&config, seed);
+    let tiles = TileSettings::from_config(&config);
+    // isométrica: 45° alrededor de la isla y la inclinación de la diagonal del cubo
+    if lens.isometric { camera.set_orbit(std::f32::consts::FRAC_PI_4, 0.5_f32.sqrt().atan(), 25.0); }
+    // en ortográfica los rayos salen del plano de la cámara: se la lleva al radio máximo de la órbita
+    // (25 vóxeles), que ya deja el plano fuera de la isla; más lejos solo se sumaría niebla
+    if lens.projection == Projection::Orthographic { camera.set_radius(25.0); }
+    // con una posición fija se arranca en la cámara libre, mirando hacia donde miraba la órbita
+    let mut fly: Option<FlyCamera> = config.get_vec3("position").map(|p| {
+        let mut cam = FlyCamera::from_orbit(&camera);
+        cam.position = p;
+        cam
+    });
+
+    // el mundo ya no cambia de vóxeles: su huella identifica la escena entre procesos
+    let fingerprint = world.fingerprint();
+    if let Some(mut worker) = worker {
+        let mut lit = None;
+        let result = worker.serve(fingerprint, |job| {
+            // la luz (y las cáusticas) solo se recalculan si cambió el momento
+            if lit != Some((job.time, job.hours, job.seed)) {
+                cycle.hours = job.hours;
+                world.set_time(job.time);
+                update_lighting(&mut skybox, &cycle, &mut caustics, &world, &water, job.seed);
+                lit = Some((job.time, job.hours, job.seed));
+            }
+            lens.fov = job.fov;
+            lens.focus_distance = job.focus_distance;
+            let view = FixedCamera { position: job.position, basis: job.basis };
+            let scene = Scene { world: &world, sky: &skybox, tex: &textures, chest_front: &chest_front_tex, chest_side: &chest_side_tex, clouds: &clouds, fog: &fog, water: &water, caustics: &caustics, time: job.time, env_samples, sss_samples, max_depth: MAX_DEPTH, seed: job.seed };
+            let pixel = pixel_tracer(&scene, &view, &lens, job.width, job.height, (0.0, 0.0), false);
+            job.pixels().collect::<Vec<_>>().into_par_iter().map(|i| pixel(i).0).collect()
+        });
+        if let Err(err) = result {
+            eprintln!("[worker] {}", err);
+            // la carpeta temporal se borra antes de salir con error
+            drop(worker);
+            std::process::exit(1);
+        }
+        return;
+    }
+    let coordinator = config.get_str("coordinator").is_some();
+    if coordinator && (denoiser.enabled || !aov::passes_from_config(&config).is_empty()) {
+        eprintln!("[coordinator] denoising and AOV passes are not available in distributed renders");
+    }
+
+    // sin ventana: la animación de cámara como secuencia de PNG numerados
+    if let Some(path) = config.get_str("animation") {
+        let anim = match CameraAnimation::load(path) {
+            Ok(anim) => anim,
+            Err(err) => { eprintln!("[animation] {}", err); return; }
+        };
+        let dir = config.get_str("frames_dir").unwrap_or("frames");
+        if let Err(err) = std::fs::create_dir_all(dir) { eprintln!("[animation] {}: {}", dir, err); return; }
+        let fps = config.get_f32("fps", 30.0).max(1.0);
+        let frames = (anim.duration * fps).floor() as u32 + 1;
+        let (w, h) = output_size(&config, &lens);
+        // lo que no anime el archivo queda como en la vista inicial
+        let start: &dyn Camera = match &fly { Some(cam) => cam, None => &camera };
+        let (start_pos, start_target) = (start.position(), start.position() + start.basis().0 * 10.0);
+        let frame_file = |frame: u32| format!("{}/frame_{:05}.png", dir, frame);
+        let pending = (0..frames).filter(|&f| !std::path::Path::new(&frame_file(f)).exists()).count();
+        // se escribe aparte y se renombra, para que un cuadro a medias no cuente como hecho
+        let write_frame = |frame: u32, pixels: &[Color]| {
+            let tmp = format!("{}/frame_{:05}.tmp.png", dir, frame);
+            save_png(&tmp, w, h, pixels).and_then(|_| std::fs::rename(&tmp, frame_file(frame)).map_err(|e| e.to_string()))
+        };
+        let started = std::time::Instant::now();
+        let mut rendered = 0;
+        let mut jobs = Vec::new();
+        for frame in 0..frames {
+            let t = frame as f32 / fps;
+            let pose = anim.sample(t);
+            match pose.hours { Some(hours) => cycle.hours = hours.rem_euclid(24.0), None => if frame > 0 { cycle.advance(1.0 / fps) } }
+            // reanudar: los cuadros que ya están escritos se saltan (el reloj sí avanza)
+            let file = frame_file(frame);
+            if std::path::Path::new(&file).exists() { continue; }
+            if let Some(fov) = pose.fov { lens.fov = fov.clamp(1.0, 170.0); }
+            let view = LookAtCamera { position: pose.position.unwrap_or(start_pos), target: pose.target.unwrap_or(start_target) };
+            world.set_time(t);
+            lens.autofocus(&view, &world);
+            // el coordinador solo arma el trabajo: la luz y las cáusticas las calcula cada trabajador
+            if coordinator { jobs.push(remote_job(frame, (w, h), (0, 0, w, h), t, cycle.hours, seed, &view, &lens)); continue; }
+            update_lighting(&mut skybox, &cycle, &mut caustics, &world, &water, seed);
+            let scene = Scene { world: &world, sky: &skybox, tex: &textures, chest_front: &chest_front_tex, chest_side: &chest_side_tex, clouds: &clouds, fog: &fog, water: &water, caustics: &caustics, time: t, env_samples, sss_samples, max_depth: MAX_DEPTH, seed };
+            let (pixels, aovs) = render(&scene, &view, &lens, w, h, (0.0, 0.0), denoiser.enabled);
+            let pixels = denoiser.apply(&pixels, &aovs, w, h);
+            tonemap.meter(&pixels, if rendered == 0 { f32::INFINITY } else { 1.0 / fps });
+            let pixels = post.apply(tonemap.apply(&pixels), &pixels, tonemap.scale(), w, h, frame as u64);
+            if let Err(err) = write_frame(frame, &pixels) {
+                eprintln!("[animation] {}", err);
+                return;
+            }
+            rendered += 1;
+            let per_frame = started.elapsed().as_secs_f32() / rendered as f32;
+            eprintln!("[animation] {} ({}/{}), ~{:.0}s left", file, frame + 1, frames, per_frame * (pending - rendered) as f32);
+        }
+        if coordinator {
+            // los cuadros llegan en cualquier orden: cada uno se mide por su cuenta, sin adaptación
+            let result = distributed::coordinate(&config, fingerprint, jobs, |job, pixels| {
+                tonemap.meter(&pixels, f32::INFINITY);
+                let pixels = post.apply(tonemap.apply(&pixels), &pixels, tonemap.scale(), w, h, job.id as u64);
+                if let Err(err) = write_frame(job.id, &pixels) { eprintln!("\n[animation] {}", err); }
+            });
+            if let Err(err) = result { eprintln!("[coordinator] {}", err); }
+        }
+        return;
+    }
+
+    // sin ventana: un solo cuadro a archivo (panoramas, estéreo, renders por lotes)
+    if let Some(path) = config.get_str("output") {
+        let view: &dyn Camera = match &fly { Some(cam) => cam, None => &camera };
+        lens.autofocus(view, &world);
+        let (w, h) = output_size(&config, &lens);
+        eprintln!("[render] {}x{} -> {}", w, h, path);
+        if coordinator {
+            let jobs = tiles.rects(w, h).into_iter().enumerate().map(|(i, rect)| remote_job(i as u32, (w, h), rect, 0.0, cycle.hours, seed, view, &lens)).collect();
+            let mut pixels = vec![Color::black(); (w * h) as usize];
+            let result = distributed::coordinate(&config, fingerprint, jobs, |job, block| {
+                for (i, c) in job.pixels().zip(block) { pixels[i] = c; }
+            });
+            match result {
+                Ok(()) => if let Err(err) = save_image(path, (w, h), &pixels, &[], &[], &mut tonemap, &post) { eprintln!("[render] {}", err); },
+                Err(err) => eprintln!("[coordinator] {}", err),
+            }
+            return;
+        }
+        update_lighting(&mut skybox, &cycle, &mut caustics, &world, &water, seed);
+        let scene = Scene { world: &world, sky: &skybox, tex: &textures, chest_front: &chest_front_tex, chest_side: &chest_side_tex, clouds: &clouds, fog: &fog, water: &water, caustics: &caustics, time: 0.0, env_samples, sss_samples, max_depth: MAX_DEPTH, seed };
+        let passes = aov::passes_from_config(&config);
+        let cancel = CancelToken::default();
+        // `--time-limit` corta el render y guarda lo que haya
+        let limit = config.get_f32("time_limit", 0.0);
+        if limit > 0.0 {
+            let token = cancel.clone();
+            std::thread::spawn(move || { std::thread::sleep(std::time::Duration::from_secs_f32(limit)); token.cancel(); });
+        }
+        let progress = Progress::new(true);
+        let pixel = pixel_tracer(&scene, view, &lens, w, h, (0.0, 0.0), denoiser.enabled || !passes.is_empty());
+        let (pixels, aovs) = tiles.render(w, h, &cancel, &progress, pixel);
+        let pixels = denoiser.apply(&pixels, &aovs, w, h);
+        let path = if cancel.is_cancelled() { partial_path(path) } else { path.to_string() };
+        if cancel.is_cancelled() { eprintln!("\n[render] cancelled at {}, saving {}", progress.status(), path); }
+        if let Err(err) = save_image(&path, (w, h), &pixels, &aovs, &passes, &mut tonemap, &post) { eprintln!("[render] {}", err); }
+        return;
+    }
 
-    let src_w = fb.width();
-    let src_h = fb.height();
+    if coordinator { eprintln!("[coordinator] needs --output or --animation"); return; }
+
+    let (mut rl, thread) = raylib::init()
+        .size(WIDTH as i32, HEIGHT as i32)
+        .title("Raytracer 3D - Proyecto2 (raylib)")
+        .build();
+    rl.set_target_fps(60);
+    if fly.is_some() { rl.disable_cursor(); }
+    // se traza a menos resolución y la GPU estira la imagen a la ventana
+    let render_scale = config.get_f32("render_scale", RENDER_SCALE).clamp(0.05, 1.0);
+    let internal_w = ((WIDTH as f32 * render_scale) as u32).max(1);
+    let internal_h = ((HEIGHT as f32 * render_scale) as u32).max(1);
+    let mut fb = RLFramebuffer::new(internal_w, internal_h);
+    if config.get_str("upscale") == Some("bilinear") { fb.filter = raylib::consts::TextureFilter::TEXTURE_FILTER_BILINEAR; }
+    let mut dynres = DynamicResolution::from_config(&config, render_scale);
+    let mut temporal = TemporalFilter::from_config(&config);
+    let mut last_pose = None;
+    // mientras se navega, un mapa de cáusticas más liviano
+    caustics.set_preview(true);
+    let mut time: f32 = 0.0;
+    let mut frame: u64 = 0;
     while !rl.window_should_close() {
+    let dt = rl.get_frame_time();
+    time += dt;
+    world.set_time(time);
+    cycle.advance(dt);
 
     let rot_speed = 1.0/30.0 * std::f32::consts::PI; 
     use raylib::prelude::KeyboardKey::*;
-    if rl.is_key_down(KEY_LEFT) { camera.orbit_delta(-rot_speed, 0.0); }
-    if rl.is_key_down(KEY_RIGHT) { camera.orbit_delta(rot_speed, 0.0); }
-    if rl.is_key_down(KEY_UP) { camera.orbit_delta(0.0, rot_speed*0.5); }
-    if rl.is_key_down(KEY_DOWN) { camera.orbit_delta(0.0, -rot_speed*0.5); }
-    if rl.is_key_down(KEY_Q) || rl.is_key_down(KEY_Z) || rl.is_key_down(KEY_MINUS) { camera.zoom_mul(0.98); }
-    if rl.is_key_down(KEY_E) || rl.is_key_down(KEY_X) || rl.is_key_down(KEY_EQUAL) { camera.zoom_mul(1.02); }
-    if rl.is_key_down(KEY_J) || rl.is_key_down(KEY_A) { sun_az -= 0.03; }
-    if rl.is_key_down(KEY_L) || rl.is_key_down(KEY_D) { sun_az += 0.03; }
-    if rl.is_key_down(KEY_I) || rl.is_key_down(KEY_W) { sun_el = (sun_el + 0.03).min(1.45); }
-    if rl.is_key_down(KEY_K) || rl.is_key_down(KEY_S) { sun_el = (sun_el - 0.03).max(0.10); }
-        let ce = sun_el.cos();
-        let se = sun_el.sin();
-        let sun_dir = color::Vec3::new( sun_az.cos() * ce, -se, sun_az.sin() * ce ).normalized();
-
-        let aspect = src_w as f32 / src_h as f32;
-
-     
-        let mut pixels: Vec<Color> = vec![Color::black(); (src_w * src_h) as usize];
-        pixels.par_iter_mut().enumerate().for_each(|(i, px)| {
-            let x = (i as u32) % src_w;
-            let y = (i as u32) / src_w;
-            let u = x as f32 / (src_w - 1) as f32;
-            let v = y as f32 / (src_h - 1) as f32;
-            let ray = camera.generate_ray(u, v, aspect);
-            let col = trace(ray, &world, MAX_DEPTH, sun_dir, &skybox, &textures, &chest_front_tex, &chest_side_tex);
-            *px = col;
-        });
-   
-        fb.replace_buffer(pixels.iter().map(|c| *c).collect());
+    // F alterna entre la órbita y la cámara libre (que arranca desde la vista actual)
+    if rl.is_key_pressed(KEY_F) {
+        fly = match fly {
+            Some(_) => { rl.enable_cursor(); None }
+            None => { rl.disable_cursor(); Some(FlyCamera::from_orbit(&camera)) }
+        };
+    }
+    if let Some(cam) = fly.as_mut() {
+        let mouse = rl.get_mouse_delta();
+        cam.look(mouse.x * 0.003, -mouse.y * 0.003);
+        if rl.is_key_down(KEY_LEFT) { cam.look(-rot_speed * 0.5, 0.0); }
+        if rl.is_key_down(KEY_RIGHT) { cam.look(rot_speed * 0.5, 0.0); }
+        if rl.is_key_down(KEY_UP) { cam.look(0.0, rot_speed * 0.5); }
+        if rl.is_key_down(KEY_DOWN) { cam.look(0.0, -rot_speed * 0.5); }
+        let axis = |pos: bool, neg: bool| pos as i32 as f32 - neg as i32 as f32;
+        let forward = axis(rl.is_key_down(KEY_W), rl.is_key_down(KEY_S));
+        let right = axis(rl.is_key_down(KEY_D), rl.is_key_down(KEY_A));
+        let up = axis(rl.is_key_down(KEY_SPACE), rl.is_key_down(KEY_LEFT_SHIFT) || rl.is_key_down(KEY_C));
+        cam.fly(forward, right, up, dt);
+        if rl.is_key_down(KEY_Q) { cam.roll_delta(-dt); }
+        if rl.is_key_down(KEY_E) { cam.roll_delta(dt); }
+        let wheel = rl.get_mouse_wheel_move();
+        if wheel != 0.0 { cam.speed_mul(1.2_f32.powf(wheel)); }
+        if rl.is_key_pressed(KEY_EQUAL) { cam.speed_mul(2.0); eprintln!("[camera] speed {}", cam.speed); }
+        if rl.is_key_pressed(KEY_MINUS) { cam.speed_mul(0.5); eprintln!("[camera] speed {}", cam.speed); }
+    } else {
+        if rl.is_key_down(KEY_LEFT) { camera.orbit_delta(-rot_speed, 0.0); }
+        if rl.is_key_down(KEY_RIGHT) { camera.orbit_delta(rot_speed, 0.0); }
+        if rl.is_key_down(KEY_UP) { camera.orbit_delta(0.0, rot_speed*0.5); }
+        if rl.is_key_down(KEY_DOWN) { camera.orbit_delta(0.0, -rot_speed*0.5); }
+        // en ortográfica el acercamiento cambia cuánto se ve, no la distancia
+        let zoom = if rl.is_key_down(KEY_Q) || rl.is_key_down(KEY_Z) || rl.is_key_down(KEY_MINUS) { 0.98 }
+            else if rl.is_key_down(KEY_E) || rl.is_key_down(KEY_X) || rl.is_key_down(KEY_EQUAL) { 1.02 } else { 1.0 };
+        if lens.projection == Projection::Orthographic { lens.ortho_size *= zoom; } else { camera.zoom_mul(zoom); }
+        if rl.is_key_down(KEY_A) { cycle.scrub(-0.05); }
+        if rl.is_key_down(KEY_D) { cycle.scrub(0.05); }
+        if rl.is_key_pressed(KEY_W) { cycle.speed *= 2.0; eprintln!("[time] speed {} h/s", cycle.speed); }
+        if rl.is_key_pressed(KEY_S) { cycle.speed *= 0.5; eprintln!("[time] speed {} h/s", cycle.speed); }
+    }
+    if rl.is_key_down(KEY_J) { cycle.scrub(-0.05); }
+    if rl.is_key_down(KEY_L) { cycle.scrub(0.05); }
+    if rl.is_key_pressed(KEY_I) { cycle.speed *= 2.0; eprintln!("[time] speed {} h/s", cycle.speed); }
+    if rl.is_key_pressed(KEY_K) { cycle.speed *= 0.5; eprintln!("[time] speed {} h/s", cycle.speed); }
+    if rl.is_key_pressed(KEY_N) { denoiser.enabled = !denoiser.enabled; eprintln!("[denoise] {}", if denoiser.enabled { "on" } else { "off" }); }
+    if rl.is_key_pressed(KEY_LEFT_BRACKET) { denoiser.strength *= 0.7; eprintln!("[denoise] strength {:.2}", denoiser.strength); }
+    if rl.is_key_pressed(KEY_RIGHT_BRACKET) { denoiser.strength /= 0.7; eprintln!("[denoise] strength {:.2}", denoiser.strength); }
+    if rl.is_key_pressed(KEY_T) { cycle.paused = !cycle.paused; eprintln!("[time] {} {}", cycle.clock_string(), if cycle.paused { "paused" } else { "running" }); }
+        update_lighting(&mut skybox, &cycle, &mut caustics, &world, &water, seed);
+
+        let view: &dyn Camera = match &fly { Some(cam) => cam, None => &camera };
+        lens.autofocus(view, &world);
+        let scene = Scene { world: &world, sky: &skybox, tex: &textures, chest_front: &chest_front_tex, chest_side: &chest_side_tex, clouds: &clouds, fog: &fog, water: &water, caustics: &caustics, time,
+            env_samples: dynres.samples(env_samples), sss_samples: dynres.samples(sss_samples), max_depth: dynres.max_depth(MAX_DEPTH), seed };
+
+        let scale = dynres.scale() / render_scale;
+        fb.resize((internal_w as f32 * scale).round() as u32, (internal_h as f32 * scale).round() as u32);
+        let (src_w, src_h) = (fb.width(), fb.height());
+        let started = std::time::Instant::now();
+        let (pixels, aovs) = render(&scene, view, &lens, src_w, src_h, temporal.jitter_offset(), denoiser.enabled || temporal.enabled);
+        let pixels = temporal.apply(pixels, &aovs, view, &lens, src_w, src_h);
+        let pixels = denoiser.apply(&pixels, &aovs, src_w, src_h);
+        tonemap.meter(&pixels, dt);
+        frame += 1;
+        fb.replace_buffer(post.apply(tonemap.apply(&pixels), &pixels, tonemap.scale(), src_w, src_h, frame));
+        let (p, f) = (view.position(), view.basis().0);
+        let pose = [p.x, p.y, p.z, f.x, f.y, f.z];
+        dynres.update(started.elapsed().as_secs_f32(), last_pose.is_some_and(|last| last != pose));
+        last_pose = Some(pose);
         if rl.is_key_pressed(KEY_P) { fb.save("render.png"); }
+        // O: render a tamaño completo y calidad completa, por bloques; otra O lo cancela y guarda lo hecho
+        if rl.is_key_pressed(KEY_O) {
+            let path = config.get_str("still").unwrap_or("still.png");
+            let (w, h) = output_size(&config, &lens);
+            let passes = aov::passes_from_config(&config);
+            // las cáusticas también con todos los fotones
+            caustics.set_preview(false);
+            update_lighting(&mut skybox, &cycle, &mut caustics, &world, &water, seed);
+            let full = Scene { world: &world, sky: &skybox, tex: &textures, chest_front: &chest_front_tex, chest_side: &chest_side_tex, clouds: &clouds, fog: &fog, water: &water, caustics: &caustics, time, env_samples, sss_samples, max_depth: MAX_DEPTH, seed };
+            let pixel = pixel_tracer(&full, view, &lens, w, h, (0.0, 0.0), denoiser.enabled || !passes.is_empty());
+            let cancel = CancelToken::default();
+            let progress = Progress::new(true);
+            eprintln!("[render] {}x{} -> {}", w, h, path);
+            let (pixels, aovs) = std::thread::scope(|s| {
+                let job = s.spawn(|| tiles.render(w, h, &cancel, &progress, pixel));
+                // la tecla solo cuenta después de un cuadro: antes todavía se ve la pulsación que lanzó el render
+                let mut presented = false;
+                while !job.is_finished() {
+                    if presented && rl.is_key_pressed(KEY_O) { cancel.cancel(); }
+                    fb.status = Some((format!("{} -> {} (O cancels)", progress.status(), path), progress.fraction()));
+                    fb.present(&mut rl, &thread);
+                    presented = true;
+                }
+                job.join().unwrap()
+            });
+            fb.status = None;
+            let pixels = denoiser.apply(&pixels, &aovs, w, h);
+            let path = if cancel.is_cancelled() { partial_path(path) } else { path.to_string() };
+            if cancel.is_cancelled() { eprintln!("\n[render] cancelled at {}, saving {}", progress.status(), path); }
+            if let Err(err) = save_image(&path, (w, h), &pixels, &aovs, &passes, &mut tonemap, &post) { eprintln!("[render] {}", err); }
+            caustics.set_preview(true);
+        }
         fb.present(&mut rl, &thread);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Una isla chica con agua, nieve, nubes, niebla y desenfoque: todo lo que usa secuencias aleatorias.
+    fn render_with_threads(threads: usize) -> Vec<u32> {
+        let args = ["--clouds", "volumetric", "--fog", "true", "--god-rays", "4", "--aperture", "0.2", "--dof-samples", "2", "--env-samples", "2"];
+        let config = Config::parse(args.iter().map(|a| a.to_string()).collect());
+        let mut world = VoxelWorld::new();
+        let params = IslandParams { top_radius: 4, top_height: 3, plateau_variation: 0, depth: 3 };
+        let ground = Material::new_basic(Color::new(0.4, 0.3, 0.2), 0.35, 24.0, MaterialKind::Terrain);
+        build_island(&mut world, ground, ground, Material::new_leaves(Color::new(0.18, 0.55, 0.22)), Material::new_stone(Color::new(0.5, 0.5, 0.52)), params);
+        for x in -1..=1 { world.add_voxel(x, 3, 0, Material::new_water(Color::new(0.25, 0.4, 0.55))); }
+        world.recompute_exposed();
+        let clouds = CloudLayer::from_config(&config, world.take_clouds());
+        cover_with_snow(&mut world, Material::new_snow(Color::new(0.92, 0.94, 0.97)));
+        let fog = Fog::from_config(&config, 0.0);
+        let water = WaterMedium::from_config(&config);
+        let mut caustics = CausticMap::from_config(&config);
+        let mut skybox = Skybox::new();
+        let cycle = DayCycle::from_config(&config, 10.0);
+        update_lighting(&mut skybox, &cycle, &mut caustics, &world, &water, 7);
+        let textures = Textures::load_folder("textures", &config);
+        let (front, side) = (load_texture("textures/cofre-frontal.png"), load_texture("textures/cofre-lado.png"));
+        let scene = Scene { world: &world, sky: &skybox, tex: &textures, chest_front: &front, chest_side: &side, clouds: &clouds, fog: &fog, water: &water, caustics: &caustics, time: 1.5, env_samples: 2, sss_samples: 2, max_depth: MAX_DEPTH, seed: 7 };
+        let camera = OrbitCamera::new(color::Vec3::new(0.0, 3.0, 0.0), 10.0);
+        let lens = Lens::from_config(&config);
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        let (pixels, _) = pool.install(|| render(&scene, &camera, &lens, 24, 18, (0.0, 0.0), false));
+        pixels.iter().flat_map(|c| [c.r.to_bits(), c.g.to_bits(), c.b.to_bits()]).collect()
+    }
+
+    #[test]
+    fn render_does_not_depend_on_thread_count() {
+        assert_eq!(render_with_threads(1), render_with_threads(4));
+    }
+}
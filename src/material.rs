@@ -9,6 +9,7 @@ pub enum MaterialKind {
 	Water,  
 	Stone,
     Cloud, 
+	Snow,
 }
 
 #[derive(Clone, Copy)]
@@ -21,20 +22,37 @@ pub struct Material {
 	pub reflectivity: f32,   
 	pub transparency: f32,  
 	pub ior: f32,           
+
+	// luz que atraviesa materiales delgados (hojas): fracción, color que toma y grosor en vóxeles
+	pub translucency: f32,
+	pub scatter_color: Color,
+	pub thickness: f32,
+	// > 0: dispersión subsuperficial por caminata aleatoria (nieve, hielo, cera), en vóxeles
+	pub mean_free_path: f32,
 }
 
 impl Material {
 	pub fn new_basic(color: Color, specular: f32, shininess: f32, kind: MaterialKind) -> Self {
-		Self { color, specular, shininess, kind, reflectivity: 0.0, transparency: 0.0, ior: 1.0 }
+		Self { color, specular, shininess, kind, ..Self::opaque() }
+	}
+	fn opaque() -> Self {
+		Self { color: Color::white(), specular: 0.0, shininess: 1.0, kind: MaterialKind::Terrain, reflectivity: 0.0, transparency: 0.0, ior: 1.0,
+			translucency: 0.0, scatter_color: Color::white(), thickness: 1.0, mean_free_path: 0.0 }
 	}
 	pub fn new_glass(color: Color, ior: f32, reflectivity: f32, transparency: f32) -> Self {
-		Self { color, specular: 0.9, shininess: 180.0, kind: MaterialKind::Glass, reflectivity, transparency, ior }
+		Self { color, specular: 0.9, shininess: 180.0, kind: MaterialKind::Glass, reflectivity, transparency, ior, ..Self::opaque() }
 	}
 	pub fn new_water(color: Color) -> Self {
-		Self { color, specular: 0.5, shininess: 64.0, kind: MaterialKind::Water, reflectivity: 0.0, transparency: 0.80, ior: 1.33 }
+		Self { color, specular: 0.5, shininess: 64.0, kind: MaterialKind::Water, reflectivity: 0.0, transparency: 0.80, ior: 1.33, ..Self::opaque() }
 	}
 	pub fn new_stone(color: Color) -> Self {
-		Self { color, specular: 0.15, shininess: 18.0, kind: MaterialKind::Stone, reflectivity: 0.0, transparency: 0.0, ior: 1.0 }
+		Self { color, specular: 0.15, shininess: 18.0, kind: MaterialKind::Stone, reflectivity: 0.0, transparency: 0.0, ior: 1.0, ..Self::opaque() }
+	}
+	pub fn new_leaves(color: Color) -> Self {
+		Self { translucency: 0.7, scatter_color: Color::new(0.75, 0.95, 0.35), thickness: 0.8, ..Self::new_basic(color, 0.08, 8.0, MaterialKind::Leaves) }
+	}
+	pub fn new_snow(color: Color) -> Self {
+		Self { scatter_color: Color::new(0.85, 0.93, 1.0), mean_free_path: 0.2, ..Self::new_basic(color, 0.25, 30.0, MaterialKind::Snow) }
 	}
 
 }
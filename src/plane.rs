@@ -24,6 +24,6 @@ impl SceneObject for Plane {
 
     let u = position.x * 0.2_f32;
     let v = position.z * 0.2_f32;
-    Some(HitInfo { t, position, normal, material: self.material, object_id: ObjectId::Plane, u: u.fract(), v: v.fract() })
+    Some(HitInfo { t, position, normal, material: self.material, object_id: ObjectId::Plane, u: u.fract(), v: v.fract(), tangent: Vec3::new(1.0, 0.0, 0.0), bitangent: Vec3::new(0.0, 0.0, 1.0) })
     }
 }
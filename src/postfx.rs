@@ -0,0 +1,251 @@
+use rayon::prelude::*;
+use crate::color::Color;
+use crate::config::Config;
+use crate::sampling::Sampler;
+
+// el grano usa secuencias aparte de las de los píxeles con la misma semilla
+const GRAIN_STREAM: u64 = 0x67_7261_696e;
+
+// Efectos de postproceso sobre la imagen ya mapeada, antes de mostrarla o guardarla. Trabajan en
+// valores lineales de pantalla ([0, 1]); el bloom toma además los brillos de la imagen HDR.
+enum Effect {
+    Bloom { threshold: f32, intensity: f32, radius: f32 },
+    Vignette { amount: f32, softness: f32 },
+    Grain { amount: f32, seed: u64 },
+    Lut { lut: CubeLut, strength: f32 },
+    Sharpen { amount: f32 },
+    Chromatic { amount: f32 },
+}
+
+pub struct PostStack { effects: Vec<Effect> }
+
+// LUT 3D en formato .cube (Adobe/Resolve): N³ colores con el rojo variando más rápido.
+struct CubeLut { size: usize, min: [f32; 3], max: [f32; 3], data: Vec<Color> }
+
+impl CubeLut {
+    fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let (mut size, mut min, mut max, mut data) = (0usize, [0.0; 3], [1.0; 3], Vec::new());
+        let triple = |parts: &[&str]| -> Option<[f32; 3]> {
+            if parts.len() != 3 { return None; }
+            Some([parts[0].parse().ok()?, parts[1].parse().ok()?, parts[2].parse().ok()?])
+        };
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            match parts.first().copied() {
+                None | Some("TITLE") => {}
+                Some("LUT_3D_SIZE") => size = parts.get(1).and_then(|s| s.parse().ok()).ok_or_else(|| format!("{}: bad LUT_3D_SIZE", path))?,
+                Some("LUT_1D_SIZE") => return Err(format!("{}: 1D LUTs are not supported", path)),
+                Some("DOMAIN_MIN") => min = triple(&parts[1..]).ok_or_else(|| format!("{}: bad DOMAIN_MIN", path))?,
+                Some("DOMAIN_MAX") => max = triple(&parts[1..]).ok_or_else(|| format!("{}: bad DOMAIN_MAX", path))?,
+                Some(_) => {
+                    let [r, g, b] = triple(&parts).ok_or_else(|| format!("{}: bad line '{}'", path, line))?;
+                    data.push(Color::new(r, g, b));
+                }
+            }
+        }
+        if size < 2 || data.len() != size * size * size {
+            return Err(format!("{}: expected {}³ entries, found {}", path, size, data.len()));
+        }
+        eprintln!("[post] loaded LUT {} ({}³)", path, size);
+        Ok(Self { size, min, max, data })
+    }
+
+    // Interpolación trilineal; la entrada va codificada en sRGB, como la espera un LUT de pantalla.
+    fn lookup(&self, c: Color) -> Color {
+        let n = self.size - 1;
+        let coord = |v: f32, k: usize| {
+            let t = ((v - self.min[k]) / (self.max[k] - self.min[k]).max(1e-6)).clamp(0.0, 1.0) * n as f32;
+            let i = (t.floor() as usize).min(n - 1);
+            (i, t - i as f32)
+        };
+        let ((r0, fr), (g0, fg), (b0, fb)) = (coord(c.r, 0), coord(c.g, 1), coord(c.b, 2));
+        let at = |r: usize, g: usize, b: usize| self.data[(b * self.size + g) * self.size + r];
+        let lerp = |a: Color, b: Color, t: f32| a * (1.0 - t) + b * t;
+        let c00 = lerp(at(r0, g0, b0), at(r0 + 1, g0, b0), fr);
+        let c10 = lerp(at(r0, g0 + 1, b0), at(r0 + 1, g0 + 1, b0), fr);
+        let c01 = lerp(at(r0, g0, b0 + 1), at(r0 + 1, g0, b0 + 1), fr);
+        let c11 = lerp(at(r0, g0 + 1, b0 + 1), at(r0 + 1, g0 + 1, b0 + 1), fr);
+        lerp(lerp(c00, c10, fg), lerp(c01, c11, fg), fb)
+    }
+}
+
+fn bilinear(img: &[Color], w: usize, h: usize, x: f32, y: f32) -> Color {
+    let x = x.clamp(0.0, (w - 1) as f32);
+    let y = y.clamp(0.0, (h - 1) as f32);
+    let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+    let (x1, y1) = ((x0 + 1).min(w - 1), (y0 + 1).min(h - 1));
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+    let top = img[y0 * w + x0] * (1.0 - fx) + img[y0 * w + x1] * fx;
+    let bottom = img[y1 * w + x0] * (1.0 - fx) + img[y1 * w + x1] * fx;
+    top * (1.0 - fy) + bottom * fy
+}
+
+// Desenfoque gaussiano separable (primero filas, después columnas).
+fn gaussian_blur(img: &[Color], w: usize, h: usize, sigma: f32) -> Vec<Color> {
+    let r = (sigma * 3.0).ceil().max(1.0) as i32;
+    let kernel: Vec<f32> = (-r..=r).map(|i| (-(i * i) as f32 / (2.0 * sigma * sigma)).exp()).collect();
+    let norm: f32 = kernel.iter().sum();
+    let pass = |src: &[Color], horizontal: bool| -> Vec<Color> {
+        (0..w * h).into_par_iter().map(|i| {
+            let (x, y) = ((i % w) as i32, (i / w) as i32);
+            let mut acc = Color::black();
+            for (k, &wk) in kernel.iter().enumerate() {
+                let d = k as i32 - r;
+                let (sx, sy) = if horizontal { ((x + d).clamp(0, w as i32 - 1), y) } else { (x, (y + d).clamp(0, h as i32 - 1)) };
+                acc = acc + src[sy as usize * w + sx as usize] * wk;
+            }
+            acc * (1.0 / norm)
+        }).collect()
+    };
+    pass(&pass(img, true), false)
+}
+
+impl PostStack {
+    // `--post bloom,vignette,lut` fija qué efectos se aplican y en qué orden; `seed` es la semilla global.
+    pub fn from_config(cfg: &Config, seed: u64) -> Self {
+        let mut effects = Vec::new();
+        for name in cfg.get_str("post").unwrap_or("").split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            let effect = match name {
+                "bloom" => Effect::Bloom {
+                    threshold: cfg.get_f32("bloom_threshold", 1.0),
+                    intensity: cfg.get_f32("bloom_intensity", 0.3),
+                    radius: cfg.get_f32("bloom_radius", 0.02).max(1e-3),
+                },
+                "vignette" => Effect::Vignette { amount: cfg.get_f32("vignette", 0.4), softness: cfg.get_f32("vignette_softness", 0.6).max(0.01) },
+                "grain" => Effect::Grain { amount: cfg.get_f32("grain", 0.04), seed },
+                "lut" => match cfg.get_str("lut").map(CubeLut::load) {
+                    Some(Ok(lut)) => Effect::Lut { lut, strength: cfg.get_f32("lut_strength", 1.0).clamp(0.0, 1.0) },
+                    Some(Err(err)) => { eprintln!("[post] {}", err); continue; }
+                    None => { eprintln!("[post] lut needs --lut file.cube"); continue; }
+                },
+                "sharpen" => Effect::Sharpen { amount: cfg.get_f32("sharpen", 0.5) },
+                "chromatic" => Effect::Chromatic { amount: cfg.get_f32("chromatic", 0.004) },
+                other => { eprintln!("[post] unknown effect '{}'", other); continue; }
+            };
+            effects.push(effect);
+        }
+        Self { effects }
+    }
+
+    // `display` es la salida del mapeo de tonos (sRGB); `hdr` la radiancia lineal y `exposure` el
+    // factor que le aplicó la exposición. `frame` cambia el grano de un cuadro a otro.
+    pub fn apply(&self, display: Vec<Color>, hdr: &[Color], exposure: f32, width: u32, height: u32, frame: u64) -> Vec<Color> {
+        if self.effects.is_empty() { return display; }
+        let (w, h) = (width as usize, height as usize);
+        let mut img: Vec<Color> = display.par_iter().map(|c| c.srgb_to_linear()).collect();
+        for effect in &self.effects {
+            img = match *effect {
+                Effect::Bloom { threshold, intensity, radius } => {
+                    // lo que pasa del umbral se desenfoca a un cuarto de resolución y se suma
+                    let (sw, sh) = (w.div_ceil(4), h.div_ceil(4));
+                    let bright: Vec<Color> = (0..sw * sh).into_par_iter().map(|i| {
+                        let (sx, sy) = (i % sw * 4, i / sw * 4);
+                        let mut acc = Color::black();
+                        let mut count = 0.0;
+                        for y in sy..(sy + 4).min(h) {
+                            for x in sx..(sx + 4).min(w) {
+                                let c = hdr[y * w + x] * exposure;
+                                acc = acc + Color::new((c.r - threshold).max(0.0), (c.g - threshold).max(0.0), (c.b - threshold).max(0.0));
+                                count += 1.0;
+                            }
+                        }
+                        acc * (1.0 / count)
+                    }).collect();
+                    let glow = gaussian_blur(&bright, sw, sh, radius * sh as f32);
+                    img.par_iter().enumerate().map(|(i, &c)| {
+                        let g = bilinear(&glow, sw, sh, ((i % w) as f32 + 0.5) / 4.0 - 0.5, ((i / w) as f32 + 0.5) / 4.0 - 0.5);
+                        (c + g * intensity).clamped()
+                    }).collect()
+                }
+                Effect::Vignette { amount, softness } => img.par_iter().enumerate().map(|(i, &c)| {
+                    // distancia al centro, 1 en las esquinas
+                    let dx = ((i % w) as f32 + 0.5) / w as f32 * 2.0 - 1.0;
+                    let dy = ((i / w) as f32 + 0.5) / h as f32 * 2.0 - 1.0;
+                    let d = ((dx * dx + dy * dy) * 0.5).sqrt();
+                    let t = ((d - (1.0 - softness)) / softness).clamp(0.0, 1.0);
+                    c * (1.0 - amount * t * t * (3.0 - 2.0 * t))
+                }).collect(),
+                Effect::Grain { amount, seed } => img.par_iter().enumerate().map(|(i, &c)| {
+                    // ruido gaussiano aproximado, más visible en los medios tonos como en la película
+                    let mut rng = Sampler::stream(seed ^ GRAIN_STREAM, i as u64, frame);
+                    let noise = rng.next_f32() + rng.next_f32() + rng.next_f32() - 1.5;
+                    let l = c.luminance().clamp(0.0, 1.0);
+                    (c + Color::white() * (noise * amount * 4.0 * l * (1.0 - l))).clamped()
+                }).collect(),
+                Effect::Lut { ref lut, strength } => img.par_iter().map(|&c| {
+                    let graded = lut.lookup(c.linear_to_srgb()).clamped().srgb_to_linear();
+                    c * (1.0 - strength) + graded * strength
+                }).collect(),
+                Effect::Sharpen { amount } => img.par_iter().enumerate().map(|(i, &c)| {
+                    // máscara de enfoque con la cruz de vecinos
+                    let (x, y) = (i % w, i / w);
+                    let n = |dx: i32, dy: i32| img[(y as i32 + dy).clamp(0, h as i32 - 1) as usize * w + (x as i32 + dx).clamp(0, w as i32 - 1) as usize];
+                    let blur = (n(-1, 0) + n(1, 0) + n(0, -1) + n(0, 1)) * 0.25;
+                    (c + (c - blur) * amount).clamped()
+                }).collect(),
+                Effect::Chromatic { amount } => (0..w * h).into_par_iter().map(|i| {
+                    // rojo y azul se separan hacia afuera y hacia adentro, más cerca de los bordes
+                    let (x, y) = ((i % w) as f32, (i / w) as f32);
+                    let (cx, cy) = (w as f32 * 0.5, h as f32 * 0.5);
+                    let (dx, dy) = (x - cx, y - cy);
+                    let s = amount * (dx * dx + dy * dy).sqrt() / cx.max(cy);
+                    let r = bilinear(&img, w, h, x + dx * s, y + dy * s).r;
+                    let b = bilinear(&img, w, h, x - dx * s, y - dy * s).b;
+                    Color::new(r, img[i].g, b)
+                }).collect(),
+            };
+        }
+        img.par_iter().map(|c| c.linear_to_srgb()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(name: &str, text: &str) -> Result<CubeLut, String> {
+        let path = std::env::temp_dir().join(format!("proyecto2-{}-{}.cube", name, std::process::id()));
+        std::fs::write(&path, text).unwrap();
+        let lut = CubeLut::load(path.to_str().unwrap());
+        let _ = std::fs::remove_file(&path);
+        lut
+    }
+
+    fn close(a: Color, b: Color) -> bool { (a.r - b.r).abs() < 1e-5 && (a.g - b.g).abs() < 1e-5 && (a.b - b.b).abs() < 1e-5 }
+
+    #[test]
+    fn identity_lut_keeps_colors() {
+        let text = "TITLE \"identidad\"\n# rojo más rápido\nLUT_3D_SIZE 2\n\n\
+                    0 0 0\n1 0 0\n0 1 0\n1 1 0\n0 0 1\n1 0 1\n0 1 1\n1 1 1\n";
+        let lut = load("identity", text).unwrap();
+        for c in [Color::new(0.0, 0.0, 0.0), Color::new(0.25, 0.5, 0.75), Color::new(1.0, 0.1, 0.9)] {
+            assert!(close(lut.lookup(c), c), "{:?}", c);
+        }
+        // fuera del dominio se recorta
+        assert!(close(lut.lookup(Color::new(2.0, -1.0, 0.5)), Color::new(1.0, 0.0, 0.5)));
+    }
+
+    #[test]
+    fn domain_rescales_the_input() {
+        let text = "LUT_3D_SIZE 2\nDOMAIN_MIN 0 0 0\nDOMAIN_MAX 2 2 2\n\
+                    0 0 0\n1 0 0\n0 1 0\n1 1 0\n0 0 1\n1 0 1\n0 1 1\n1 1 1\n";
+        let lut = load("domain", text).unwrap();
+        assert!(close(lut.lookup(Color::new(1.0, 0.5, 2.0)), Color::new(0.5, 0.25, 1.0)));
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        for (text, msg) in [
+            ("LUT_1D_SIZE 4\n", "1D LUTs"),
+            ("LUT_3D_SIZE dos\n", "bad LUT_3D_SIZE"),
+            ("LUT_3D_SIZE 2\nDOMAIN_MAX 1 1\n", "bad DOMAIN_MAX"),
+            ("LUT_3D_SIZE 2\n0 0 0\n1 0 x\n", "bad line"),
+            ("LUT_3D_SIZE 2\n0 0 0\n1 0 0\n", "expected 2³ entries, found 2"),
+        ] {
+            let err = load("bad", text).err().unwrap();
+            assert!(err.contains(msg), "{}", err);
+        }
+    }
+}
@@ -18,6 +18,15 @@ pub struct HitInfo {
 	pub object_id: ObjectId,
 	pub u: f32,
 	pub v: f32,
+	pub tangent: Vec3,   // dirección en que crece u
+	pub bitangent: Vec3, // dirección en que crece v
+}
+
+// Marco tangente de una cara alineada a los ejes, con la misma convención de uv que usan los cubos.
+pub fn face_tangents(normal: Vec3) -> (Vec3, Vec3) {
+	if normal.x.abs() > 0.9 { (Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 1.0, 0.0)) }
+	else if normal.y.abs() > 0.9 { (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0)) }
+	else { (Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)) }
 }
 
 pub trait SceneObject {
@@ -0,0 +1,76 @@
+// Generador pseudoaleatorio pequeño (PCG32) para las integrales de Monte Carlo del trazador.
+// Cada muestra de cada píxel tiene su propia secuencia (ver `stream`), así que la imagen es
+// reproducible y no depende de cuántos hilos la trazan.
+pub struct Sampler { state: u64 }
+
+// Mezclador de splitmix64: semillas vecinas dan estados sin relación.
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E3779B97F4A7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D049BB133111EB);
+    x ^ (x >> 31)
+}
+
+impl Sampler {
+    pub fn new(seed: u64) -> Self {
+        let mut r = Sampler { state: 0 };
+        r.next_u32();
+        r.state = r.state.wrapping_add(seed);
+        r.next_u32();
+        r
+    }
+
+    // Secuencia de la muestra `sample` del píxel `pixel` con la semilla global `seed`.
+    pub fn stream(seed: u64, pixel: u64, sample: u64) -> Self {
+        Self::new(mix(mix(seed ^ mix(pixel)) ^ sample))
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    // Uniforme en [0, 1).
+    pub fn next_f32(&mut self) -> f32 { (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn first(mut s: Sampler, n: usize) -> Vec<u32> { (0..n).map(|_| s.next_u32()).collect() }
+
+    #[test]
+    fn same_seed_gives_the_same_sequence() {
+        assert_eq!(first(Sampler::new(42), 16), first(Sampler::new(42), 16));
+        assert_ne!(first(Sampler::new(42), 16), first(Sampler::new(43), 16));
+        assert_eq!(first(Sampler::stream(7, 100, 3), 16), first(Sampler::stream(7, 100, 3), 16));
+    }
+
+    #[test]
+    fn streams_differ_per_seed_pixel_and_sample() {
+        let base = first(Sampler::stream(7, 100, 3), 8);
+        for other in [Sampler::stream(8, 100, 3), Sampler::stream(7, 101, 3), Sampler::stream(7, 100, 4), Sampler::stream(7, 3, 100)] {
+            assert_ne!(first(other, 8), base);
+        }
+    }
+
+    #[test]
+    fn floats_are_uniform_in_the_unit_interval() {
+        let mut s = Sampler::new(1);
+        let n = 100_000;
+        let mut buckets = [0u32; 10];
+        let mut sum = 0.0f64;
+        for _ in 0..n {
+            let v = s.next_f32();
+            assert!((0.0..1.0).contains(&v));
+            buckets[(v * 10.0) as usize] += 1;
+            sum += v as f64;
+        }
+        assert!((sum / n as f64 - 0.5).abs() < 0.01);
+        assert!(buckets.iter().all(|&b| (b as i32 - n / 10).abs() < n / 100), "{:?}", buckets);
+    }
+}
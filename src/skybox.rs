@@ -1,25 +1,239 @@
 use crate::color::{Color, Vec3};
+use crate::envmap::EnvMap;
 
+// Cielo analítico de Preetham (1999): la luminancia y cromaticidad de cada dirección
+// dependen del ángulo al cenit, del ángulo al sol y de la turbidez del aire.
+const SKY_SCALE: f32 = 0.045;          // kcd/m² -> radiancia relativa (el sol directo ronda 1)
+const SUN_ANGULAR_RADIUS: f32 = 0.0087; // ~0.5°, algo más grande que el real para que se vea
+const SUN_DISK_INTENSITY: f32 = 12.0;
+const SKY_AMBIENT_STRENGTH: f32 = 0.35; // el cielo analítico ilumina las sombras solo en parte
+const MOON_ANGULAR_RADIUS: f32 = 0.012;
+const NIGHT_SKY: Color = Color { r: 0.003, g: 0.005, b: 0.012 };
+const STAR_CELLS: f32 = 160.0; // celdas por unidad en la rejilla 3D que reparte las estrellas
 
-pub struct Skybox;
+pub struct Skybox {
+    to_sun: Vec3,
+    turbidity: f32,
+    perez_y: [f32; 5],
+    perez_x: [f32; 5],
+    perez_yc: [f32; 5],
+    zenith: (f32, f32, f32), // (Y, x, y) en el cenit
+    sun_color: Color,
+    sky_irradiance: Color,
+    ground_color: Color,
+    // panorama HDR opcional: reemplaza al modelo analítico como fondo, reflejos y luz ambiente
+    environment: Option<EnvMap>,
+    direct_sun: bool, // con panorama, si además se ilumina con el sol analítico
+    // cielo nocturno
+    to_moon: Vec3,
+    moon_light: Color,
+    moon_illumination: f32, // fracción iluminada del disco
+    latitude: f32,          // radianes, para orientar el campo de estrellas
+    sidereal: f32,          // tiempo sidéreo local, radianes
+}
+
+fn perez(c: &[f32; 5], cos_theta: f32, gamma: f32, cos_gamma: f32) -> f32 {
+    (1.0 + c[0] * (c[1] / cos_theta.max(0.01)).exp()) * (1.0 + c[2] * (c[3] * gamma).exp() + c[4] * cos_gamma * cos_gamma)
+}
+
+fn xyy_to_rgb(y_lum: f32, x: f32, y: f32) -> Color {
+    if y <= 1e-4 { return Color::black(); }
+    let cx = x / y * y_lum;
+    let cz = (1.0 - x - y) / y * y_lum;
+    Color::new(
+        (3.2406 * cx - 1.5372 * y_lum - 0.4986 * cz).max(0.0),
+        (-0.9689 * cx + 1.8758 * y_lum + 0.0415 * cz).max(0.0),
+        (0.0557 * cx - 0.2040 * y_lum + 1.0570 * cz).max(0.0),
+    )
+}
 
 impl Skybox {
-    pub fn new() -> Self { Skybox }
+    pub fn new() -> Self {
+        let mut sky = Skybox {
+            to_sun: Vec3::new(0.0, 1.0, 0.0), turbidity: 2.5,
+            perez_y: [0.0; 5], perez_x: [0.0; 5], perez_yc: [0.0; 5],
+            zenith: (0.0, 0.0, 0.0), sun_color: Color::white(), sky_irradiance: Color::black(), ground_color: Color::black(),
+            environment: None, direct_sun: true,
+            to_moon: Vec3::new(0.0, -1.0, 0.0), moon_light: Color::black(), moon_illumination: 0.0, latitude: 0.0, sidereal: 0.0,
+        };
+        sky.set_sun(Vec3::new(0.3, 0.8, 0.2));
+        sky
+    }
+
+    // Luna y estrellas. Llamar antes de `set_sun`, que es quien recalcula la luz ambiente.
+    pub fn set_night(&mut self, to_moon: Vec3, moon_light: Color, moon_illumination: f32, latitude: f32, sidereal: f32) {
+        self.to_moon = to_moon.normalized();
+        // se apaga al ponerse, igual que el sol
+        self.moon_light = moon_light * ((self.to_moon.y + 0.02) / 0.08).clamp(0.0, 1.0);
+        self.moon_illumination = moon_illumination;
+        self.latitude = latitude;
+        self.sidereal = sidereal;
+    }
+
+    // 0 de día, 1 con el sol bien por debajo del horizonte.
+    fn night(&self) -> f32 { ((0.05 - self.to_sun.y) / 0.2).clamp(0.0, 1.0) }
+
+    // Recalcula el modelo para una nueva dirección hacia el sol.
+    pub fn set_sun(&mut self, to_sun: Vec3) {
+        self.to_sun = to_sun.normalized();
+        let t = self.turbidity;
+        // el modelo no está definido bajo el horizonte; se congela ahí y se atenúa aparte
+        let theta_s = self.to_sun.y.clamp(0.02, 1.0).acos();
+        let (th, th2, th3) = (theta_s, theta_s * theta_s, theta_s * theta_s * theta_s);
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (std::f32::consts::PI - 2.0 * theta_s);
+        let yz = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+        let xz = t * t * (0.00166 * th3 - 0.00375 * th2 + 0.00209 * th)
+            + t * (-0.02903 * th3 + 0.06377 * th2 - 0.03202 * th + 0.00394)
+            + (0.11693 * th3 - 0.21196 * th2 + 0.06052 * th + 0.25886);
+        let ycz = t * t * (0.00275 * th3 - 0.00610 * th2 + 0.00317 * th)
+            + t * (-0.04214 * th3 + 0.08970 * th2 - 0.04153 * th + 0.00516)
+            + (0.15346 * th3 - 0.26756 * th2 + 0.06670 * th + 0.26688);
+        self.zenith = (yz, xz, ycz);
+        self.perez_y = [0.1787 * t - 1.4630, -0.3554 * t + 0.4275, -0.0227 * t + 5.3251, 0.1206 * t - 2.5771, -0.0670 * t + 0.3703];
+        self.perez_x = [-0.0193 * t - 0.2592, -0.0665 * t + 0.0008, -0.0004 * t + 0.2125, -0.0641 * t - 0.8989, -0.0033 * t + 0.0452];
+        self.perez_yc = [-0.0167 * t - 0.2608, -0.0950 * t + 0.0092, -0.0079 * t + 0.2102, -0.0441 * t - 1.6537, -0.0109 * t + 0.0529];
+
+        // luz del sol tras atravesar la atmósfera (Rayleigh + aerosoles, masa de aire de Kasten-Young)
+        let elev_deg = 90.0 - theta_s.to_degrees();
+        let air_mass = 1.0 / (theta_s.cos() + 0.50572 * (elev_deg + 6.07995).powf(-1.6364));
+        let beta = 0.04608 * t - 0.04586;
+        let tau = |lambda_um: f32| 0.008735 * lambda_um.powf(-4.08) + beta * lambda_um.powf(-1.3);
+        let horizon_fade = ((self.to_sun.y + 0.05) / 0.1).clamp(0.0, 1.0);
+        self.sun_color = Color::new(
+            (-tau(0.65) * air_mass).exp(),
+            (-tau(0.57) * air_mass).exp(),
+            (-tau(0.475) * air_mass).exp(),
+        ) * (1.15 * horizon_fade);
+
+        // irradiancia del hemisferio superior (coseno-ponderada) para la luz ambiente
+        let mut irr = Color::black();
+        let mut weight = 0.0;
+        for i in 0..6 { for j in 0..12 {
+            let el = (i as f32 + 0.5) / 6.0 * std::f32::consts::FRAC_PI_2;
+            let az = j as f32 / 12.0 * std::f32::consts::TAU;
+            let d = Vec3::new(el.cos() * az.cos(), el.sin(), el.cos() * az.sin());
+            let w = el.sin() * el.cos();
+            irr = irr + self.sky_radiance(d) * w;
+            weight += w;
+        }}
+        self.sky_irradiance = irr * (1.0 / weight);
+        let ground_albedo = Color::new(0.32, 0.30, 0.28);
+        self.ground_color = ground_albedo * (self.sky_irradiance + self.sun_color * self.to_sun.y.max(0.0) * 0.5);
+    }
+
+    pub fn set_environment(&mut self, env: EnvMap, direct_sun: bool) {
+        self.environment = Some(env);
+        self.direct_sun = direct_sun;
+    }
+    pub fn environment(&self) -> Option<&EnvMap> { self.environment.as_ref() }
+
+    // Color/intensidad de la luz solar directa.
+    // Luz directa principal: (dirección hacia la luz, color). El sol, o la luna si brilla más.
+    pub fn key_light(&self) -> (Vec3, Color) {
+        if !self.direct_sun { return (self.to_sun, Color::black()); }
+        let lum = |c: Color| c.r + c.g + c.b;
+        if lum(self.moon_light) > lum(self.sun_color) { (self.to_moon, self.moon_light) } else { (self.to_sun, self.sun_color) }
+    }
+    // Luz ambiente que recibe una superficie con normal `n` (cielo arriba, suelo abajo).
+    pub fn ambient(&self, n: Vec3) -> Color {
+        if let Some(env) = &self.environment { return env.diffuse(n); }
+        let k = 0.5 + 0.5 * n.y;
+        (self.sky_irradiance * k + self.ground_color * (1.0 - k)) * SKY_AMBIENT_STRENGTH
+    }
+
+    // Radiancia del cielo (sin disco solar); por debajo del horizonte se usa la del horizonte.
+    fn sky_radiance(&self, d: Vec3) -> Color {
+        let cos_theta = d.y.max(0.001);
+        let cos_gamma = d.dot(self.to_sun).clamp(-1.0, 1.0);
+        let gamma = cos_gamma.acos();
+        let cos_ts = self.to_sun.y.clamp(0.02, 1.0);
+        let theta_s = cos_ts.acos();
+        let (yz, xz, ycz) = self.zenith;
+        let lum = yz * perez(&self.perez_y, cos_theta, gamma, cos_gamma) / perez(&self.perez_y, 1.0, theta_s, cos_ts);
+        let x = xz * perez(&self.perez_x, cos_theta, gamma, cos_gamma) / perez(&self.perez_x, 1.0, theta_s, cos_ts);
+        let y = ycz * perez(&self.perez_yc, cos_theta, gamma, cos_gamma) / perez(&self.perez_yc, 1.0, theta_s, cos_ts);
+        let night_fade = ((self.to_sun.y + 0.1) / 0.15).clamp(0.0, 1.0);
+        let day = xyy_to_rgb(lum * SKY_SCALE * night_fade, x, y);
+        // de noche: fondo azul muy oscuro y el halo de la luna
+        let night = self.night();
+        if night <= 0.0 { return day; }
+        let halo = (40.0 * (d.dot(self.to_moon) - 1.0)).exp() * 0.03 * self.moon_illumination;
+        day + (NIGHT_SKY + Color::new(0.7, 0.75, 0.9) * halo) * night
+    }
+
+    // Estrellas fijas en coordenadas ecuatoriales, giradas según el tiempo sidéreo.
+    fn stars(&self, d: Vec3) -> Color {
+        let (sl, cl) = self.latitude.sin_cos();
+        let (east, north, up) = (d.x, -d.z, d.y);
+        let sin_dec = (north * cl + up * sl).clamp(-1.0, 1.0);
+        let hour_angle = (-east).atan2(up * cl - north * sl);
+        let ra = self.sidereal - hour_angle;
+        let cos_dec = (1.0 - sin_dec * sin_dec).sqrt();
+        let c = Vec3::new(cos_dec * ra.cos(), sin_dec, cos_dec * ra.sin());
+        let (ix, iy, iz) = ((c.x * STAR_CELLS).floor() as i32, (c.y * STAR_CELLS).floor() as i32, (c.z * STAR_CELLS).floor() as i32);
+        let mut h = (ix.wrapping_mul(73856093) ^ iy.wrapping_mul(19349663) ^ iz.wrapping_mul(83492791)) as u32;
+        h = (h ^ (h >> 15)).wrapping_mul(2246822519);
+        h ^= h >> 13;
+        if h & 0xff > 6 { return Color::black(); }
+        let jitter = |bits: u32| 0.2 + 0.6 * ((h >> bits) & 0xff) as f32 / 255.0;
+        let star = Vec3::new(ix as f32 + jitter(8), iy as f32 + jitter(16), iz as f32 + jitter(24)) * (1.0 / STAR_CELLS);
+        let dist = (c - star.normalized()).length();
+        let brightness = (((h >> 4) & 0xf) as f32 / 15.0).powi(3) * 3.0 + 0.15;
+        let tint = crate::daycycle::kelvin_to_rgb(3500.0 + 9000.0 * ((h >> 20) & 0xf) as f32 / 15.0);
+        tint * (brightness * (-(dist / 0.0015).powi(2)).exp())
+    }
+
+    // Disco lunar con su fase: cada punto del disco es una esfera iluminada desde el sol.
+    fn moon_disk(&self, d: Vec3) -> Option<Color> {
+        let m = self.to_moon;
+        if d.dot(m) < MOON_ANGULAR_RADIUS.cos() { return None; }
+        // con la luna casi en el cenit (o el nadir) el eje vertical no sirve de referencia
+        let reference = if m.y.abs() > 0.999 { Vec3::new(0.0, 0.0, 1.0) } else { Vec3::new(0.0, 1.0, 0.0) };
+        let right = m.cross(reference).normalized();
+        let upv = right.cross(m).normalized();
+        let x = d.dot(right) / MOON_ANGULAR_RADIUS;
+        let y = d.dot(upv) / MOON_ANGULAR_RADIUS;
+        let r2 = x * x + y * y;
+        if r2 > 1.0 { return None; }
+        let n = -m * (1.0 - r2).sqrt() + right * x + upv * y;
+        let lit = n.dot(self.to_sun).max(0.0);
+        Some(Color::new(1.3, 1.28, 1.2) * lit + Color::new(0.02, 0.022, 0.03))
+    }
+
+    // Reflejo especular del panorama para superficies no espejadas (None con el cielo analítico,
+    // donde el brillo ya lo aporta el sol).
+    pub fn env_specular(&self, dir: Vec3, shininess: f32) -> Option<Color> {
+        self.environment.as_ref().map(|env| env.glossy(dir, shininess))
+    }
+
+    // Color de la niebla mirando hacia `dir`: el cielo cerca del horizonte, sin sol ni estrellas.
+    pub fn fog_color(&self, dir: Vec3) -> Color {
+        let flat = Vec3::new(dir.x, 0.08, dir.z).normalized();
+        if let Some(env) = &self.environment { return env.glossy(flat, 4.0); }
+        self.sky_radiance(flat)
+    }
+
     pub fn sample(&self, dir: Vec3) -> Color {
         let d = dir.normalized();
-        let t = (d.y + 1.0) * 0.5;
-        let horizon = Color::new(0.78, 0.83, 0.92);
-        let ground = Color::black();
-        let zenith = Color::new(0.22, 0.46, 0.93);
-        if t < 0.40 {
-            let k = t / 0.40; ground * (1.0 - k) + horizon * k
-        } else if t < 0.55 {
-
-            horizon
-        } else if t < 0.85 {
-            let k = (t - 0.55) / 0.30; horizon * (1.0 - k) + zenith * k
-        } else {
-            zenith
+        if let Some(env) = &self.environment { return env.radiance(d); }
+        let sky = self.sky_radiance(d);
+        if d.y < 0.0 {
+            // bruma del horizonte que se funde con el color del suelo
+            let k = (-d.y / 0.25).min(1.0);
+            return sky * (1.0 - k) + self.ground_color * k;
+        }
+        if let Some(moon) = self.moon_disk(d) { return sky + moon; }
+        let night = self.night();
+        let sky = if night > 0.0 { sky + self.stars(d) * night } else { sky };
+        let cos_gamma = d.dot(self.to_sun);
+        let cos_disk = SUN_ANGULAR_RADIUS.cos();
+        if cos_gamma > cos_disk {
+            // oscurecimiento hacia el borde del disco
+            let r = ((1.0 - cos_gamma) / (1.0 - cos_disk)).min(1.0);
+            let limb = 1.0 - 0.6 * (1.0 - (1.0 - r).sqrt());
+            return sky + self.sun_color * (SUN_DISK_INTENSITY * limb);
         }
+        sky
     }
 }
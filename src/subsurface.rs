@@ -0,0 +1,80 @@
+use std::f32::consts::TAU;
+use crate::color::{Color, Vec3};
+use crate::material::Material;
+use crate::sampling::Sampler;
+use crate::voxel_world::VoxelWorld;
+
+const EPS: f32 = 4e-4;
+
+// Luz que atraviesa materiales delgados (hojas) y sale hacia la cámara. Se atenúa con el
+// número de vóxeles translúcidos que cruza desde `origin` hacia la luz; si la tapa algo opaco,
+// o si no cruzó ninguno (la luz directa ya la pone el sombreado normal), no aporta nada.
+pub fn translucency(world: &VoxelWorld, m: &Material, origin: Vec3, n: Vec3, view_dir: Vec3, to_light: Vec3) -> Color {
+    if m.translucency <= 0.0 { return Color::black(); }
+    let crossed = match world.translucent_path(origin, to_light, 200.0) {
+        Some(k) if k > 0 => k,
+        _ => return Color::black(),
+    };
+    let atten = (-(crossed as f32) / m.thickness.max(0.01)).exp();
+    // difusión a través de la hoja más un lóbulo hacia adelante cuando se mira a contraluz
+    let diffuse = n.dot(to_light).abs();
+    let forward = view_dir.dot(-to_light).max(0.0).powi(4);
+    m.scatter_color * (m.translucency * atten * (diffuse + forward))
+}
+
+fn uniform_sphere(rng: &mut Sampler) -> Vec3 {
+    let z = 1.0 - 2.0 * rng.next_f32();
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = TAU * rng.next_f32();
+    Vec3::new(r * phi.cos(), z, r * phi.sin())
+}
+
+// Dirección en el hemisferio de `n`, con densidad proporcional al coseno.
+fn cosine_hemisphere(n: Vec3, rng: &mut Sampler) -> Vec3 {
+    let (r, phi) = (rng.next_f32().sqrt(), TAU * rng.next_f32());
+    let t = if n.x.abs() > 0.9 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) };
+    let u = n.cross(t).normalized();
+    let v = n.cross(u);
+    u * (r * phi.cos()) + v * (r * phi.sin()) + n * (1.0 - r * r).max(0.0).sqrt()
+}
+
+// Caminata aleatoria dentro de los vóxeles del mismo material: pasos exponenciales con el
+// recorrido libre medio, cada choque tiñe con `scatter_color`, y al salir se mira cuánta luz
+// directa llega a ese punto. Reemplaza al término difuso de Lambert.
+pub fn random_walk(world: &VoxelWorld, m: &Material, position: Vec3, normal: Vec3, to_light: Vec3, samples: u32, rng: &mut Sampler) -> Color {
+    let inside = |p: Vec3| world.material_at(p).is_some_and(|v| v.kind == m.kind);
+    let mut acc = Color::black();
+    for _ in 0..samples {
+        let mut p = position - normal * EPS;
+        // primera dirección: hacia adentro, con distribución coseno
+        let mut dir = cosine_hemisphere(-normal, rng);
+        let mut throughput = Color::white();
+        for _ in 0..64 {
+            let t = -(1.0 - rng.next_f32()).ln() * m.mean_free_path;
+            if inside(p + dir * t) {
+                p += dir * t;
+                throughput = throughput * m.scatter_color;
+                dir = uniform_sphere(rng);
+                continue;
+            }
+            // salió: se busca el borde por bisección y la cara por la que cruzó
+            let (mut a, mut b) = (0.0, t);
+            for _ in 0..10 {
+                let mid = 0.5 * (a + b);
+                if inside(p + dir * mid) { a = mid; } else { b = mid; }
+            }
+            let last = p + dir * a;
+            let exit = p + dir * b;
+            let local = exit - Vec3::new(last.x.round(), last.y.round(), last.z.round());
+            let n_out = if local.x.abs() >= local.y.abs() && local.x.abs() >= local.z.abs() { Vec3::new(local.x.signum(), 0.0, 0.0) }
+                else if local.y.abs() >= local.z.abs() { Vec3::new(0.0, local.y.signum(), 0.0) }
+                else { Vec3::new(0.0, 0.0, local.z.signum()) };
+            let ndl = n_out.dot(to_light);
+            if ndl > 0.0 && !world.occluded(exit + n_out * (EPS * 6.0), to_light, 200.0) {
+                acc = acc + throughput * ndl;
+            }
+            break;
+        }
+    }
+    acc * (1.0 / samples.max(1) as f32)
+}
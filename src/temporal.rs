@@ -0,0 +1,124 @@
+use rayon::prelude::*;
+use crate::aov::{AovSample, MISS_DEPTH};
+use crate::camera::{Camera, Lens};
+use crate::color::{Color, Vec3};
+use crate::config::Config;
+
+// Vista con la que se trazó el cuadro anterior.
+struct View { position: Vec3, basis: (Vec3, Vec3, Vec3), lens: Lens, aspect: f32 }
+
+// Cuadro anterior: color acumulado, lo que vio cada píxel y cuántos cuadros lleva acumulando.
+struct History { view: View, width: u32, height: u32, color: Vec<Color>, aovs: Vec<AovSample>, age: Vec<f32> }
+
+// Reproyección temporal (TAA): cada punto visible se proyecta en la cámara del cuadro anterior
+// y se mezcla con el color acumulado allí, si era la misma superficie (pruebas de plano y de
+// normal); las zonas recién descubiertas arrancan de cero. Cada cuadro el rayo se corre un
+// poco dentro del píxel, así la acumulación además suaviza los bordes.
+pub struct TemporalFilter {
+    pub enabled: bool,
+    pub jitter: bool,
+    alpha: f32,       // peso mínimo del cuadro nuevo; más bajo = más acumulación y más estela
+    clip: f32,        // ancho de la caja de color (en desvíos) a la que se recorta la historia
+    frame: u32,
+    history: Option<History>,
+}
+
+const MAX_AGE: f32 = 32.0;
+
+// Secuencia de Halton en base `b`, para repartir los desplazamientos dentro del píxel.
+fn halton(mut i: u32, b: u32) -> f32 {
+    let (mut f, mut r) = (1.0, 0.0);
+    while i > 0 {
+        f /= b as f32;
+        r += f * (i % b) as f32;
+        i /= b;
+    }
+    r
+}
+
+impl TemporalFilter {
+    pub fn from_config(cfg: &Config) -> Self {
+        let enabled = cfg.get_bool("temporal", false);
+        Self {
+            enabled,
+            jitter: enabled && cfg.get_bool("taa_jitter", true),
+            alpha: cfg.get_f32("temporal_alpha", 0.1).clamp(0.01, 1.0),
+            clip: cfg.get_f32("temporal_clip", 1.25).max(0.0),
+            frame: 0,
+            history: None,
+        }
+    }
+
+    // Desplazamiento dentro del píxel para el próximo cuadro, en [-0.5, 0.5)².
+    pub fn jitter_offset(&self) -> (f32, f32) {
+        if !self.jitter { return (0.0, 0.0); }
+        let i = self.frame % 16 + 1;
+        (halton(i, 2) - 0.5, halton(i, 3) - 0.5)
+    }
+
+    pub fn apply(&mut self, pixels: Vec<Color>, aovs: &[AovSample], view: &dyn Camera, lens: &Lens, width: u32, height: u32) -> Vec<Color> {
+        self.frame = self.frame.wrapping_add(1);
+        let n = (width * height) as usize;
+        let aspect = width as f32 / height as f32;
+        // sin reproyección posible (panoramas, estéreo) no hay historia
+        if !self.enabled || aovs.len() != n || lens.project(view.position(), view.basis(), view.position() + view.basis().0, aspect).is_none() {
+            self.history = None;
+            return pixels;
+        }
+        let (w, h) = (width as i32, height as i32);
+        let (out, age): (Vec<Color>, Vec<f32>) = (0..n).into_par_iter().map(|i| {
+            let c = pixels[i];
+            let s = &aovs[i];
+            let Some(prev) = self.history.as_ref() else { return (c, 1.0) };
+            if s.depth >= MISS_DEPTH { return (c, 1.0); }
+            let Some((u, v)) = prev.view.lens.project(prev.view.position, prev.view.basis, s.position, prev.view.aspect) else { return (c, 1.0) };
+            // bilineal sobre los cuatro píxeles vecinos, descartando los que eran otra superficie
+            let (px, py) = (u * prev.width as f32 - 0.5, v * prev.height as f32 - 0.5);
+            let (x0, y0) = (px.floor() as i32, py.floor() as i32);
+            let (fx, fy) = (px - x0 as f32, py - y0 as f32);
+            let tolerance = 0.05 + 0.01 * s.depth;
+            let (mut hist, mut hist_age, mut total) = (Color::black(), 0.0, 0.0);
+            for (dx, dy, wt) in [(0, 0, (1.0 - fx) * (1.0 - fy)), (1, 0, fx * (1.0 - fy)), (0, 1, (1.0 - fx) * fy), (1, 1, fx * fy)] {
+                let (sx, sy) = (x0 + dx, y0 + dy);
+                if sx < 0 || sy < 0 || sx >= prev.width as i32 || sy >= prev.height as i32 || wt <= 0.0 { continue; }
+                let j = (sy * prev.width as i32 + sx) as usize;
+                let p = &prev.aovs[j];
+                if p.depth >= MISS_DEPTH || p.normal.dot(s.normal) < 0.9 || (p.position - s.position).dot(s.normal).abs() > tolerance { continue; }
+                hist = hist + prev.color[j] * wt;
+                hist_age += prev.age[j] * wt;
+                total += wt;
+            }
+            if total < 1e-3 { return (c, 1.0); }
+            let (hist, hist_age) = (hist * (1.0 / total), hist_age / total);
+            // la historia se recorta a la caja de colores del entorno actual (media ± desvío),
+            // para no arrastrar luces o sombras que ya cambiaron
+            let (x, y) = (i as i32 % w, i as i32 / w);
+            let (mut m1, mut m2, mut count) = (Color::black(), Color::black(), 0.0);
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let (sx, sy) = ((x + dx).clamp(0, w - 1), (y + dy).clamp(0, h - 1));
+                    let q = pixels[(sy * w + sx) as usize];
+                    m1 = m1 + q;
+                    m2 = m2 + q * q;
+                    count += 1.0;
+                }
+            }
+            let mean = m1 * (1.0 / count);
+            let var = m2 * (1.0 / count) - mean * mean;
+            let sd = Color::new(var.r.max(0.0).sqrt(), var.g.max(0.0).sqrt(), var.b.max(0.0).sqrt()) * self.clip;
+            let hist = Color::new(
+                hist.r.clamp(mean.r - sd.r, mean.r + sd.r),
+                hist.g.clamp(mean.g - sd.g, mean.g + sd.g),
+                hist.b.clamp(mean.b - sd.b, mean.b + sd.b),
+            );
+            let age = (hist_age + 1.0).min(MAX_AGE);
+            let a = (1.0 / age).max(self.alpha);
+            (hist * (1.0 - a) + c * a, age)
+        }).unzip();
+        self.history = Some(History {
+            view: View { position: view.position(), basis: view.basis(), lens: lens.clone(), aspect },
+            width, height, color: out.clone(), aovs: aovs.to_vec(), age,
+        });
+        out
+    }
+}
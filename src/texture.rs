@@ -1,21 +1,45 @@
-
-use crate::color::Color;
+use crate::color::{Color, Vec3};
+use crate::config::Config;
 use std::path::Path;
 
 pub struct LoadedTexture {
     pub w: u32,
     pub h: u32,
     pub data: Vec<Color>,
+    pub anim: Option<Animation>,
+}
+
+// Tira vertical de cuadros (estilo Minecraft): cada cuadro ocupa `frame_h` filas.
+pub struct Animation {
+    pub frame_h: u32,
+    pub frames: Vec<u32>,   // orden de reproducción (índices de cuadro)
+    pub frame_time: f32,    // segundos por cuadro
+    pub interpolate: bool,  // mezclar con el siguiente cuadro
 }
 
 impl LoadedTexture {
-    pub fn sample(&self, u: f32, v: f32) -> Color {
+    pub fn sample(&self, u: f32, v: f32) -> Color { self.sample_at(u, v, 0.0) }
+
+    pub fn sample_at(&self, u: f32, v: f32, time: f32) -> Color {
     if self.w == 0 || self.h == 0 { return Color::new(1.0,0.0,1.0); }
+        let Some(anim) = &self.anim else { return self.sample_rows(0, self.h, u, v); };
+        let pos = time.max(0.0) / anim.frame_time;
+        let idx = pos.floor() as usize;
+        let f0 = anim.frames[idx % anim.frames.len()];
+        let c0 = self.sample_rows(f0 * anim.frame_h, anim.frame_h, u, v);
+        if !anim.interpolate { return c0; }
+        let f1 = anim.frames[(idx + 1) % anim.frames.len()];
+        let c1 = self.sample_rows(f1 * anim.frame_h, anim.frame_h, u, v);
+        let k = pos.fract();
+        c0 * (1.0 - k) + c1 * k
+    }
+
+    fn sample_rows(&self, y0: u32, rows: u32, u: f32, v: f32) -> Color {
         let mut uu = u.fract(); if uu < 0.0 { uu += 1.0; }
         let mut vv = v.fract(); if vv < 0.0 { vv += 1.0; }
         let x = (uu * (self.w - 1) as f32).clamp(0.0, (self.w - 1) as f32) as u32;
-        let y = (vv * (self.h - 1) as f32).clamp(0.0, (self.h - 1) as f32) as u32;
-        self.data[(y * self.w + x) as usize]
+        let y = (vv * (rows - 1) as f32).clamp(0.0, (rows - 1) as f32) as u32;
+        self.data[((y0 + y) * self.w + x) as usize]
     }
 }
 
@@ -27,12 +51,106 @@ pub struct Textures {
     pub leaves: LoadedTexture,
     pub stone: LoadedTexture,
     pub water: LoadedTexture,
+    pub trunk_relief: Option<Relief>,
+    pub stone_relief: Option<Relief>,
+    pub dirt_relief: Option<Relief>,
+}
+
+// Mapa de normales en espacio tangente (convención OpenGL: verde = arriba en la imagen)
+// y mapa de alturas opcional para parallax occlusion mapping.
+pub struct Relief {
+    pub normal: LoadedTexture,
+    pub height: Option<LoadedTexture>,
+    pub depth: f32, // profundidad máxima del parallax, en unidades de vóxel
+}
+
+impl Relief {
+    // Parallax occlusion mapping: avanza por capas del mapa de alturas hasta cruzar la superficie.
+    pub fn parallax(&self, u: f32, v: f32, t: Vec3, b: Vec3, n: Vec3, view: Vec3) -> (f32, f32) {
+        let Some(height) = &self.height else { return (u, v) };
+        let vz = view.dot(n);
+        if vz <= 1e-3 { return (u, v); }
+        let layers = 8.0 + 16.0 * (1.0 - vz);
+        let layer_depth = 1.0 / layers;
+        let du = -view.dot(t) / vz * self.depth / layers;
+        let dv = -view.dot(b) / vz * self.depth / layers;
+        let (mut cu, mut cv) = (u, v);
+        let mut cur_depth = 0.0;
+        let mut map_depth = 1.0 - height.sample(cu, cv).r;
+        let mut prev_gap = map_depth;
+        while cur_depth < map_depth && cur_depth < 1.0 {
+            cu += du; cv += dv;
+            cur_depth += layer_depth;
+            prev_gap = map_depth - (cur_depth - layer_depth);
+            map_depth = 1.0 - height.sample(cu, cv).r;
+        }
+        // interpola entre las dos últimas capas
+        let after = map_depth - cur_depth;
+        let w = if (after - prev_gap).abs() > 1e-6 { after / (after - prev_gap) } else { 0.0 };
+        (cu - du * w, cv - dv * w)
+    }
+
+    pub fn perturb(&self, u: f32, v: f32, t: Vec3, b: Vec3, n: Vec3) -> Vec3 {
+        let c = self.normal.sample(u, v);
+        let (x, y, z) = (c.r * 2.0 - 1.0, c.g * 2.0 - 1.0, c.b * 2.0 - 1.0);
+        // v crece hacia abajo en la imagen, así que +y del mapa es -bitangente
+        (t * x - b * y + n * z.max(0.05)).normalized()
+    }
+}
+
+// Altura a partir de la luminancia del albedo (suavizada y normalizada a 0..1).
+fn height_from_albedo(albedo: &LoadedTexture) -> LoadedTexture {
+    let (w, h) = (albedo.w, albedo.h.min(albedo.anim.as_ref().map_or(albedo.h, |a| a.frame_h)));
+    let lum = |x: i32, y: i32| {
+        let c = albedo.data[(y.rem_euclid(h as i32) as u32 * w + x.rem_euclid(w as i32) as u32) as usize];
+        0.2126 * c.r + 0.7152 * c.g + 0.0722 * c.b
+    };
+    let mut data = Vec::with_capacity((w * h) as usize);
+    for y in 0..h as i32 { for x in 0..w as i32 {
+        let mut sum = 0.0;
+        for dy in -1..=1 { for dx in -1..=1 { sum += lum(x + dx, y + dy); } }
+        data.push(sum / 9.0);
+    }}
+    let lo = data.iter().cloned().fold(f32::MAX, f32::min);
+    let hi = data.iter().cloned().fold(f32::MIN, f32::max);
+    let range = (hi - lo).max(1e-4);
+    let data = data.into_iter().map(|l| { let k = (l - lo) / range; Color::new(k, k, k) }).collect();
+    LoadedTexture { w, h, data, anim: None }
+}
+
+// Mapa de normales por diferencias centrales del mapa de alturas.
+fn normal_from_height(height: &LoadedTexture, strength: f32) -> LoadedTexture {
+    let (w, h) = (height.w as i32, height.h as i32);
+    let at = |x: i32, y: i32| height.data[(y.rem_euclid(h) * w + x.rem_euclid(w)) as usize].r;
+    let mut data = Vec::with_capacity((w * h) as usize);
+    for y in 0..h { for x in 0..w {
+        let du = (at(x + 1, y) - at(x - 1, y)) * 0.5 * strength;
+        let dv = (at(x, y + 1) - at(x, y - 1)) * 0.5 * strength;
+        let n = Vec3::new(-du, dv, 1.0).normalized();
+        data.push(Color::new(n.x * 0.5 + 0.5, n.y * 0.5 + 0.5, n.z * 0.5 + 0.5));
+    }}
+    LoadedTexture { w: height.w, h: height.h, data, anim: None }
+}
+
+// Busca `<nombre>_n.png` (normales) y `<nombre>_h.png` (alturas) junto al albedo.
+// Sin ninguno de los dos, con `derive` se genera el relieve a partir del propio albedo.
+// `depth` es la profundidad del parallax, en fracciones de la cara.
+fn load_relief(folder: &str, name: &str, albedo: &LoadedTexture, derive: bool, depth: f32) -> Option<Relief> {
+    let n_path = format!("{}/{}_n.png", folder, name);
+    let h_path = format!("{}/{}_h.png", folder, name);
+    let has_n = Path::new(&n_path).exists();
+    let height = if Path::new(&h_path).exists() { Some(load_png(&h_path)) }
+                 else if derive && !has_n { Some(height_from_albedo(albedo)) }
+                 else { None };
+    let normal = if has_n { load_png(&n_path) }
+                 else { normal_from_height(height.as_ref()?, 2.0) };
+    Some(Relief { normal, height, depth })
 }
 
 pub fn load_png(path: &str) -> LoadedTexture {
     if !Path::new(path).exists() {
         eprintln!("[textures] missing file: {}", path);
-        return LoadedTexture { w:1, h:1, data: vec![Color::new(1.0,0.0,1.0)] };
+        return LoadedTexture { w:1, h:1, data: vec![Color::new(1.0,0.0,1.0)], anim: None };
     }
     match image::open(path) {
         Ok(img_any) => {
@@ -41,43 +159,102 @@ pub fn load_png(path: &str) -> LoadedTexture {
             let mut data = Vec::with_capacity((w*h) as usize);
             for p in img.pixels() { let [r,g,b,_a] = p.0; data.push(Color::new(r as f32/255.0, g as f32/255.0, b as f32/255.0)); }
             eprintln!("[textures] loaded {} ({}x{})", path, w, h);
-            LoadedTexture { w, h, data }
+            LoadedTexture { w, h, data, anim: None }
         }
         Err(err) => {
             eprintln!("[textures] error loading {}: {}", path, err);
-            LoadedTexture { w:1, h:1, data: vec![Color::new(1.0,0.0,1.0)] }
+            LoadedTexture { w:1, h:1, data: vec![Color::new(1.0,0.0,1.0)], anim: None }
+        }
+    }
+}
+
+// Carga un PNG y, si tiene un archivo hermano `.anim`, lo trata como una tira vertical de cuadros
+// animada (sin él, una textura alta es solo una textura alta). El `.anim` tiene líneas `clave = valor`,
+// todas opcionales (puede estar vacío):
+//   frametime = 0.12      (segundos por cuadro, 0.1 por defecto)
+//   interpolate = true    (mezclar cuadros consecutivos)
+//   frameheight = 64      (alto de cada cuadro, por defecto el ancho)
+//   frames = 0,1,2,1      (orden de reproducción, por defecto todos en orden)
+pub fn load_texture(path: &str) -> LoadedTexture {
+    let mut tex = load_png(path);
+    // los colores se guardan en sRGB; el trazador trabaja en lineal (los mapas de relieve no pasan por aquí)
+    for c in tex.data.iter_mut() { *c = c.srgb_to_linear(); }
+    let meta_path = Path::new(path).with_extension("anim");
+    let Ok(meta) = std::fs::read_to_string(&meta_path) else { return tex };
+
+    let mut frame_h = tex.w;
+    let mut frame_time = 0.1;
+    let mut interpolate = false;
+    let mut frames: Vec<u32> = Vec::new();
+    for line in meta.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let Some((key, value)) = line.split_once('=') else { continue };
+        let value = value.trim();
+        match key.trim() {
+            "frametime" => frame_time = value.parse().unwrap_or(frame_time),
+            "interpolate" => interpolate = value == "true",
+            "frameheight" => frame_h = value.parse().unwrap_or(frame_h),
+            "frames" => frames = value.split(',').filter_map(|f| f.trim().parse().ok()).collect(),
+            other => eprintln!("[textures] {}: unknown key '{}'", meta_path.display(), other),
         }
     }
+    let count = tex.h.checked_div(frame_h).unwrap_or(0);
+    if count <= 1 { return tex; }
+    frames.retain(|&f| f < count);
+    if frames.is_empty() { frames = (0..count).collect(); }
+    eprintln!("[textures] {}: {} frames, {}s each", path, frames.len(), frame_time);
+    tex.anim = Some(Animation { frame_h, frames, frame_time: f32::max(frame_time, 1e-3), interpolate });
+    tex
 }
 
 impl Textures {
-    pub fn load_folder(folder: &str) -> Self {
+    // `--relief derive|files|off`: relieve de los archivos `_n`/`_h` o, si no hay, sacado del albedo;
+    // solo de los archivos; o ninguno.
+    pub fn load_folder(folder: &str, cfg: &Config) -> Self {
+        let (relief, derive) = match cfg.get_str("relief").unwrap_or("derive") {
+            "off" | "none" | "false" => (false, false),
+            "files" => (true, false),
+            "derive" => (true, true),
+            other => { eprintln!("[textures] unknown relief mode '{}', using derive", other); (true, true) }
+        };
+        let depth = cfg.get_f32("relief_depth", 0.04).max(0.0);
+        let dirt = load_texture(&format!("{}/tierra.png", folder));
+        let trunk = load_texture(&format!("{}/tronco.png", folder));
+        let stone = load_texture(&format!("{}/stone.png", folder));
         Self {
-            grass_top: load_png(&format!("{}/arriba-cesped.png", folder)),
-            grass_side: load_png(&format!("{}/cesped.png", folder)),
-            dirt: load_png(&format!("{}/tierra.png", folder)),
-            trunk: load_png(&format!("{}/tronco.png", folder)),
-            leaves: load_png(&format!("{}/hojas.png", folder)),
-            stone: load_png(&format!("{}/stone.png", folder)),
-            water: load_png(&format!("{}/agua.png", folder)),
+            grass_top: load_texture(&format!("{}/arriba-cesped.png", folder)),
+            grass_side: load_texture(&format!("{}/cesped.png", folder)),
+            leaves: load_texture(&format!("{}/hojas.png", folder)),
+            // el agua fluye si existe la tira animada; si no, la textura fija
+            water: if Path::new(&format!("{}/agua-flujo.png", folder)).exists() {
+                load_texture(&format!("{}/agua-flujo.png", folder))
+            } else {
+                load_texture(&format!("{}/agua.png", folder))
+            },
+            trunk_relief: if relief { load_relief(folder, "tronco", &trunk, derive, depth) } else { None },
+            stone_relief: if relief { load_relief(folder, "stone", &stone, derive, depth) } else { None },
+            dirt_relief: if relief { load_relief(folder, "tierra", &dirt, derive, depth) } else { None },
+            dirt,
+            trunk,
+            stone,
         }
     }
 }
 
 
-pub fn sample_grass_from_textures(normal: crate::color::Vec3, u: f32, v: f32, tex: &Textures, is_top_exposed: bool) -> Color {
+pub fn sample_grass_from_textures(normal: crate::color::Vec3, u: f32, v: f32, tex: &Textures, is_top_exposed: bool, time: f32) -> Color {
     let ax = normal.x.abs(); let ay = normal.y.abs(); let az = normal.z.abs();
-    if !is_top_exposed { return tex.dirt.sample(u,v); }
+    if !is_top_exposed { return tex.dirt.sample_at(u,v,time); }
     if ay >= ax && ay >= az { 
-        if normal.y > 0.0 { tex.grass_top.sample(u,v) } else { tex.dirt.sample(u,v) }
+        if normal.y > 0.0 { tex.grass_top.sample_at(u,v,time) } else { tex.dirt.sample_at(u,v,time) }
     } else {
       
         let v_flipped = 1.0 - v;
-        tex.grass_side.sample(u, v_flipped)
+        tex.grass_side.sample_at(u, v_flipped, time)
     }
 }
 
-pub fn sample_trunk_from_textures(_normal: crate::color::Vec3, u: f32, v: f32, tex: &Textures) -> Color { tex.trunk.sample(u,v) }
-pub fn sample_leaves_from_textures(u: f32, v: f32, tex: &Textures) -> Color { tex.leaves.sample(u,v) }
-pub fn sample_stone_from_textures(u: f32, v: f32, tex: &Textures) -> Color { tex.stone.sample(u,v) }
-pub fn sample_water_from_textures(u: f32, v: f32, tex: &Textures) -> Color { tex.water.sample(u,v) }
\ No newline at end of file
+pub fn sample_trunk_from_textures(_normal: crate::color::Vec3, u: f32, v: f32, tex: &Textures, time: f32) -> Color { tex.trunk.sample_at(u,v,time) }
+pub fn sample_leaves_from_textures(u: f32, v: f32, tex: &Textures, time: f32) -> Color { tex.leaves.sample_at(u,v,time) }
+pub fn sample_stone_from_textures(u: f32, v: f32, tex: &Textures, time: f32) -> Color { tex.stone.sample_at(u,v,time) }
+pub fn sample_water_from_textures(u: f32, v: f32, tex: &Textures, time: f32) -> Color { tex.water.sample_at(u,v,time) }
\ No newline at end of file
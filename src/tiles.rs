@@ -0,0 +1,230 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use rayon::prelude::*;
+use crate::aov::AovSample;
+use crate::color::Color;
+use crate::config::Config;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TileOrder { Scanline, Spiral, Hilbert }
+
+// Reparto de un render grande en bloques: se trazan en el orden elegido (el centro primero con
+// la espiral) y cada hilo toma el siguiente bloque libre cuando termina el suyo.
+pub struct TileSettings { pub size: u32, pub order: TileOrder }
+
+// Se comparte entre hilos; cuando alguien la activa, los bloques pendientes no se empiezan.
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) { self.0.store(true, Ordering::Relaxed); }
+    pub fn is_cancelled(&self) -> bool { self.0.load(Ordering::Relaxed) }
+}
+
+// Avance de un render por bloques, para mostrarlo en la terminal o en la ventana.
+pub struct Progress {
+    done: AtomicUsize,
+    total: AtomicUsize,
+    started: Mutex<Instant>,
+    printed: Mutex<Instant>,
+    print: bool, // escribir el avance en la terminal
+}
+
+impl Progress {
+    pub fn new(print: bool) -> Self {
+        Self { done: AtomicUsize::new(0), total: AtomicUsize::new(0), started: Mutex::new(Instant::now()), printed: Mutex::new(Instant::now()), print }
+    }
+
+    pub fn start(&self, total: usize) {
+        self.done.store(0, Ordering::Relaxed);
+        self.total.store(total, Ordering::Relaxed);
+        *self.started.lock().unwrap() = Instant::now();
+    }
+
+    pub fn tile_done(&self) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        if !self.print { return; }
+        let mut printed = self.printed.lock().unwrap();
+        if done < self.total.load(Ordering::Relaxed) && printed.elapsed().as_secs_f32() < 0.5 { return; }
+        *printed = Instant::now();
+        eprint!("\r[render] {}   ", self.status());
+        if done == self.total.load(Ordering::Relaxed) { eprintln!(); }
+    }
+
+    pub fn fraction(&self) -> f32 {
+        let total = self.total.load(Ordering::Relaxed);
+        if total == 0 { 0.0 } else { self.done.load(Ordering::Relaxed) as f32 / total as f32 }
+    }
+
+    // Segundos que faltan, estimados con el ritmo que se lleva; None hasta terminar un bloque.
+    pub fn eta(&self) -> Option<f32> {
+        let f = self.fraction();
+        if f <= 0.0 { return None; }
+        Some(self.started.lock().unwrap().elapsed().as_secs_f32() * (1.0 - f) / f)
+    }
+
+    pub fn status(&self) -> String {
+        let (done, total) = (self.done.load(Ordering::Relaxed), self.total.load(Ordering::Relaxed));
+        match self.eta() {
+            Some(eta) => format!("{:.0}% ({}/{} tiles), {:.0}s left", self.fraction() * 100.0, done, total, eta),
+            None => format!("0% (0/{} tiles)", total),
+        }
+    }
+}
+
+// Posición de (x, y) en la curva de Hilbert que recorre una grilla de n×n (n potencia de 2).
+fn hilbert_index(n: u32, mut x: u32, mut y: u32) -> u64 {
+    let mut d = 0u64;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = (x & s > 0) as u32;
+        let ry = (y & s > 0) as u32;
+        d += s as u64 * s as u64 * ((3 * rx) ^ ry) as u64;
+        // rotar el cuadrante para que la curva siga continua
+        if ry == 0 {
+            if rx == 1 { x = s - 1 - (x & (s - 1)); y = s - 1 - (y & (s - 1)); }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    d
+}
+
+impl TileSettings {
+    pub fn from_config(cfg: &Config) -> Self {
+        let order = match cfg.get_str("tile_order").unwrap_or("spiral") {
+            "scanline" => TileOrder::Scanline,
+            "hilbert" => TileOrder::Hilbert,
+            other => {
+                if other != "spiral" { eprintln!("[tiles] unknown order '{}', using spiral", other); }
+                TileOrder::Spiral
+            }
+        };
+        Self { size: cfg.get_u32("tile_size", 32).max(4), order }
+    }
+
+    // Bloques (x, y, ancho, alto) en píxeles, en el orden en que se trazan.
+    pub fn rects(&self, width: u32, height: u32) -> Vec<(u32, u32, u32, u32)> {
+        let (nx, ny) = (width.div_ceil(self.size), height.div_ceil(self.size));
+        let mut tiles: Vec<(u32, u32)> = (0..ny).flat_map(|ty| (0..nx).map(move |tx| (tx, ty))).collect();
+        match self.order {
+            TileOrder::Scanline => {}
+            TileOrder::Spiral => {
+                // por anillos alrededor del centro y, dentro de cada anillo, por ángulo
+                let (cx, cy) = ((nx as f32 - 1.0) * 0.5, (ny as f32 - 1.0) * 0.5);
+                let key = |&(tx, ty): &(u32, u32)| {
+                    let (dx, dy) = (tx as f32 - cx, ty as f32 - cy);
+                    (dx.abs().max(dy.abs()), dy.atan2(dx))
+                };
+                tiles.sort_by(|a, b| key(a).partial_cmp(&key(b)).unwrap_or(std::cmp::Ordering::Equal));
+            }
+            TileOrder::Hilbert => {
+                let n = nx.max(ny).next_power_of_two();
+                tiles.sort_by_key(|&(tx, ty)| hilbert_index(n, tx, ty));
+            }
+        }
+        tiles.into_iter().map(|(tx, ty)| {
+            let (x, y) = (tx * self.size, ty * self.size);
+            (x, y, self.size.min(width - x), self.size.min(height - y))
+        }).collect()
+    }
+
+    // Traza la imagen por bloques con `pixel(i)`. Si se cancela, los bloques que faltaban quedan
+    // en negro (y sin pases), y la imagen sirve como resultado parcial.
+    pub fn render<F>(&self, width: u32, height: u32, cancel: &CancelToken, progress: &Progress, pixel: F) -> (Vec<Color>, Vec<AovSample>)
+    where F: Fn(usize) -> (Color, AovSample) + Sync {
+        let tiles = self.rects(width, height);
+        progress.start(tiles.len());
+        let n = (width * height) as usize;
+        let out = Mutex::new((vec![Color::black(); n], vec![AovSample::default(); n]));
+        let next = AtomicUsize::new(0);
+        // un trabajador por hilo que va tomando bloques de la lista, así se respeta el orden
+        (0..rayon::current_num_threads()).into_par_iter().for_each(|_| loop {
+            if cancel.is_cancelled() { break; }
+            let Some(&(x0, y0, tw, th)) = tiles.get(next.fetch_add(1, Ordering::Relaxed)) else { break };
+            let (x1, y1) = (x0 + tw, y0 + th);
+            let block: Vec<(Color, AovSample)> = (y0..y1).flat_map(|y| (x0..x1).map(move |x| (y * width + x) as usize)).map(&pixel).collect();
+            let mut out = out.lock().unwrap();
+            let mut k = 0;
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let i = (y * width + x) as usize;
+                    (out.0[i], out.1[i]) = block[k];
+                    k += 1;
+                }
+            }
+            drop(out);
+            progress.tile_done();
+        });
+        out.into_inner().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Traza con un solo hilo y devuelve el índice de píxel en el orden en que se pidió.
+    fn trace(order: TileOrder, width: u32, height: u32) -> (Vec<usize>, Vec<Color>, Progress) {
+        let tiles = TileSettings { size: 16, order };
+        let progress = Progress::new(false);
+        let calls = Mutex::new(Vec::new());
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let (colors, _) = pool.install(|| tiles.render(width, height, &CancelToken::default(), &progress, |i| {
+            calls.lock().unwrap().push(i);
+            (Color::new(i as f32, 0.0, 0.0), AovSample::default())
+        }));
+        (calls.into_inner().unwrap(), colors, progress)
+    }
+
+    #[test]
+    fn every_order_traces_each_pixel_once() {
+        let (width, height) = (100, 70);
+        for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+            let (calls, colors, progress) = trace(order, width, height);
+            let mut seen = vec![0; (width * height) as usize];
+            for &i in &calls { seen[i] += 1; }
+            assert!(seen.iter().all(|&n| n == 1));
+            assert!(colors.iter().enumerate().all(|(i, c)| c.r == i as f32));
+            assert_eq!(progress.fraction(), 1.0);
+        }
+    }
+
+    #[test]
+    fn spiral_starts_at_the_center_tile() {
+        let (calls, _, _) = trace(TileOrder::Spiral, 80, 80);
+        // 5×5 bloques de 16: el del centro empieza en (32, 32)
+        assert_eq!(calls[0], 32 * 80 + 32);
+        let (calls, _, _) = trace(TileOrder::Scanline, 80, 80);
+        assert_eq!(calls[0], 0);
+    }
+
+    #[test]
+    fn hilbert_visits_neighbouring_cells_in_turn() {
+        let n = 8;
+        let mut cells = vec![(0, 0); (n * n) as usize];
+        for y in 0..n {
+            for x in 0..n {
+                cells[hilbert_index(n, x, y) as usize] = (x as i32, y as i32);
+            }
+        }
+        // es una biyección (no quedan celdas repetidas) y cada paso avanza a una vecina
+        let mut sorted = cells.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted.len(), (n * n) as usize);
+        for w in cells.windows(2) {
+            assert_eq!((w[0].0 - w[1].0).abs() + (w[0].1 - w[1].1).abs(), 1, "{:?}", w);
+        }
+    }
+
+    #[test]
+    fn cancelled_render_leaves_the_image_black() {
+        let tiles = TileSettings { size: 16, order: TileOrder::Spiral };
+        let cancel = CancelToken::default();
+        cancel.cancel();
+        let (colors, _) = tiles.render(40, 30, &cancel, &Progress::new(false), |_| (Color::new(1.0, 1.0, 1.0), AovSample::default()));
+        assert!(colors.iter().all(|c| c.r == 0.0));
+    }
+}
@@ -0,0 +1,118 @@
+use rayon::prelude::*;
+use crate::color::Color;
+use crate::config::Config;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ToneMapper { Clamp, Reinhard, Aces, AgX }
+
+// Paso final de la imagen: el trazador produce radiancia lineal sin límite; aquí se expone,
+// se comprime el rango con la curva elegida y se codifica en sRGB para la pantalla o el PNG.
+pub struct ToneMap {
+    pub mapper: ToneMapper,
+    pub exposure: f32,        // EV: cada punto duplica el brillo
+    pub auto: bool,
+    key: f32,                 // gris medio al que lleva la exposición automática
+    adapt_rate: f32,          // 1/s; cuánto tarda el ojo en acostumbrarse
+    white: f32,               // blanco de Reinhard: lo que queda en 1
+    adapted: Option<f32>,     // luminancia media (geométrica) a la que ya se adaptó
+}
+
+fn mat3(m: [[f32; 3]; 3], c: Color) -> Color {
+    Color::new(
+        m[0][0] * c.r + m[0][1] * c.g + m[0][2] * c.b,
+        m[1][0] * c.r + m[1][1] * c.g + m[1][2] * c.b,
+        m[2][0] * c.r + m[2][1] * c.g + m[2][2] * c.b,
+    )
+}
+
+// Ajuste de Stephen Hill de la RRT + ODT de ACES para sRGB.
+fn aces(c: Color) -> Color {
+    const INPUT: [[f32; 3]; 3] = [[0.59719, 0.35458, 0.04823], [0.07600, 0.90834, 0.01566], [0.02840, 0.13383, 0.83777]];
+    const OUTPUT: [[f32; 3]; 3] = [[1.60475, -0.53108, -0.07367], [-0.10208, 1.10813, -0.00605], [-0.00327, -0.07276, 1.07602]];
+    let fit = |v: f32| (v * (v + 0.0245786) - 0.000090537) / (v * (0.983729 * v + 0.432951) + 0.238081);
+    let c = mat3(INPUT, c);
+    mat3(OUTPUT, Color::new(fit(c.r), fit(c.g), fit(c.b))).clamped()
+}
+
+// AgX (Troy Sobotka) en su versión mínima: espacio de trabajo algo desaturado, curva logarítmica
+// de 16.5 EV y una sigmoide polinómica. Los brillos muy fuertes van hacia el blanco sin virar de tono.
+fn agx(c: Color) -> Color {
+    const INSET: [[f32; 3]; 3] = [[0.84247905, 0.0784336, 0.079223745], [0.042328242, 0.87846863, 0.07916613], [0.042375655, 0.0784336, 0.879143]];
+    const OUTSET: [[f32; 3]; 3] = [[1.196879, -0.09802088, -0.09902974], [-0.052896852, 1.1519032, -0.098961177], [-0.052971636, -0.09804345, 1.1510737]];
+    const MIN_EV: f32 = -12.47393;
+    const MAX_EV: f32 = 4.026069;
+    let curve = |x: f32| {
+        let x = ((x.max(1e-10).log2()).clamp(MIN_EV, MAX_EV) - MIN_EV) / (MAX_EV - MIN_EV);
+        let (x2, x4) = (x * x, x * x * x * x);
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232
+    };
+    let c = mat3(INSET, c);
+    let c = mat3(OUTSET, Color::new(curve(c.r), curve(c.g), curve(c.b)));
+    // la curva devuelve valores ya codificados para pantalla (gamma 2.2); se vuelven a lineal
+    Color::new(c.r.max(0.0).powf(2.2), c.g.max(0.0).powf(2.2), c.b.max(0.0).powf(2.2))
+}
+
+impl ToneMap {
+    pub fn from_config(cfg: &Config) -> Self {
+        let mapper = match cfg.get_str("tonemap").unwrap_or("aces") {
+            "none" | "clamp" => ToneMapper::Clamp,
+            "reinhard" => ToneMapper::Reinhard,
+            "agx" => ToneMapper::AgX,
+            other => {
+                if other != "aces" { eprintln!("[tonemap] unknown tone mapper '{}', using aces", other); }
+                ToneMapper::Aces
+            }
+        };
+        Self {
+            mapper,
+            exposure: cfg.get_f32("exposure", 0.0),
+            auto: cfg.get_bool("auto_exposure", false),
+            key: cfg.get_f32("exposure_key", 0.18).max(1e-3),
+            adapt_rate: cfg.get_f32("exposure_adapt", 1.5).max(0.0),
+            white: cfg.get_f32("reinhard_white", 4.0).max(1e-3),
+            adapted: None,
+        }
+    }
+
+    // Mide la luminancia media del cuadro y acerca a ella la adaptación; `dt` infinito (o el
+    // primer cuadro) se adapta de golpe. Sin exposición automática no hace nada.
+    pub fn meter(&mut self, pixels: &[Color], dt: f32) {
+        if !self.auto || pixels.is_empty() { return; }
+        // sumas parciales por bloques fijos y en orden: el resultado no depende de cuántos hilos hay
+        let partial: Vec<f32> = pixels.par_chunks(4096).map(|chunk| chunk.iter().map(|c| (c.luminance().max(0.0) + 1e-4).ln()).sum()).collect();
+        let log_sum: f32 = partial.iter().sum();
+        let avg = (log_sum / pixels.len() as f32).exp();
+        let k = 1.0 - (-self.adapt_rate * dt).exp();
+        self.adapted = Some(match self.adapted {
+            Some(prev) if dt.is_finite() => (prev.ln() + (avg.ln() - prev.ln()) * k).exp(),
+            _ => avg,
+        });
+    }
+
+    // Factor total de exposición (manual y automática) que se aplica antes de la curva.
+    pub fn scale(&self) -> f32 {
+        let auto = match self.adapted { Some(avg) if self.auto => self.key / avg.max(1e-4), _ => 1.0 };
+        self.exposure.exp2() * auto
+    }
+
+    // Color lineal de la escena -> color de pantalla en [0, 1], codificado en sRGB.
+    fn map(&self, c: Color, scale: f32) -> Color {
+        let c = c * scale;
+        let mapped = match self.mapper {
+            ToneMapper::Clamp => c.clamped(),
+            ToneMapper::Reinhard => {
+                // sobre la luminancia, para no desaturar; el blanco indica qué valor llega a 1
+                let l = c.luminance();
+                if l <= 0.0 { Color::black() } else { (c * ((1.0 + l / (self.white * self.white)) / (1.0 + l))).clamped() }
+            }
+            ToneMapper::Aces => aces(c),
+            ToneMapper::AgX => agx(c),
+        };
+        mapped.linear_to_srgb()
+    }
+
+    pub fn apply(&self, pixels: &[Color]) -> Vec<Color> {
+        let scale = self.scale();
+        pixels.par_iter().map(|&c| self.map(c, scale)).collect()
+    }
+}
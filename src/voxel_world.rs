@@ -1,7 +1,8 @@
 use std::collections::{HashMap, HashSet};
 use crate::color::Vec3;
 use crate::material::{Material, MaterialKind};
-use crate::ray_intersect::{HitInfo, ObjectId, Ray, SceneObject};
+use crate::ray_intersect::{HitInfo, ObjectId, Ray, SceneObject, face_tangents};
+use crate::water::Waves;
 
 
 pub struct VoxelWorld {
@@ -9,17 +10,22 @@ pub struct VoxelWorld {
     exposed: HashSet<(i32,i32,i32)>, 
     min: (i32,i32,i32),
     max: (i32,i32,i32),
+    waves: Option<Waves>,
+    wave_time: f32,
+    revision: u64, // sube con cada cambio de vóxeles o de olas (no con su movimiento); lo usan las cachés que dependen del mundo
 }
 
 impl VoxelWorld {
-    pub fn new() -> Self { Self { voxels: HashMap::new(), exposed: HashSet::new(), min: (i32::MAX,i32::MAX,i32::MAX), max:(i32::MIN,i32::MIN,i32::MIN) } }
+    pub fn new() -> Self { Self { voxels: HashMap::new(), exposed: HashSet::new(), min: (i32::MAX,i32::MAX,i32::MAX), max:(i32::MIN,i32::MIN,i32::MIN), waves: None, wave_time: 0.0, revision: 0 } }
     pub fn add_voxel(&mut self, x:i32,y:i32,z:i32, mat: Material) {
         self.voxels.insert((x,y,z), mat);
+        self.revision += 1;
         self.min.0 = self.min.0.min(x); self.min.1 = self.min.1.min(y); self.min.2 = self.min.2.min(z);
         self.max.0 = self.max.0.max(x); self.max.1 = self.max.1.max(y); self.max.2 = self.max.2.max(z);
     }
     pub fn remove_voxel(&mut self, x:i32,y:i32,z:i32) {
         self.voxels.remove(&(x,y,z));
+        self.revision += 1;
       
     }
     pub fn has_voxel(&self, x:i32,y:i32,z:i32) -> bool { self.voxels.contains_key(&(x,y,z)) }
@@ -31,6 +37,108 @@ impl VoxelWorld {
         }
     }
     pub fn is_top_exposed(&self, x:i32,y:i32,z:i32) -> bool { self.exposed.contains(&(x,y,z)) }
+    pub fn set_waves(&mut self, waves: Waves) { self.waves = Some(waves); self.revision += 1; }
+    // Las olas se mueven con el tiempo; quien dependa de la superficie mira `wave_time`.
+    pub fn set_time(&mut self, time: f32) { self.wave_time = time; }
+    pub fn revision(&self) -> u64 { self.revision }
+    pub fn waves_active(&self) -> bool { self.waves.as_ref().is_some_and(|w| w.is_active()) }
+    pub fn wave_time(&self) -> f32 { self.wave_time }
+    // Resumen de vóxeles y materiales que no depende del orden; dos mundos iguales dan lo mismo.
+    pub fn fingerprint(&self) -> u64 {
+        use std::hash::{DefaultHasher, Hash, Hasher};
+        self.voxels.iter().fold(self.voxels.len() as u64, |acc, (pos, m)| {
+            let mut h = DefaultHasher::new();
+            pos.hash(&mut h);
+            // el material entero: si se le agrega un campo, esto deja de compilar hasta sumarlo
+            let Material { color, specular, shininess, kind, reflectivity, transparency, ior, translucency, scatter_color, thickness, mean_free_path } = *m;
+            (kind as u8).hash(&mut h);
+            [color.r, color.g, color.b, specular, shininess, reflectivity, transparency, ior, translucency,
+                scatter_color.r, scatter_color.g, scatter_color.b, thickness, mean_free_path].map(f32::to_bits).hash(&mut h);
+            acc.wrapping_add(h.finish())
+        })
+    }
+    // Vóxeles que desvían la luz (superficie del agua y vidrio): de ahí salen las cáusticas.
+    pub fn refractive_voxels(&self) -> Vec<(i32,i32,i32)> {
+        self.voxels.iter()
+            .filter(|&(&(x,y,z), m)| m.kind == MaterialKind::Glass || (m.kind == MaterialKind::Water && !self.is_water_voxel(x, y+1, z)))
+            .map(|(k,_m)| *k).collect()
+    }
+    pub fn max_y(&self) -> i32 { self.max.1 }
+    pub fn material_at(&self, p: Vec3) -> Option<Material> {
+        self.voxels.get(&((p.x + 0.5).floor() as i32, (p.y + 0.5).floor() as i32, (p.z + 0.5).floor() as i32)).copied()
+    }
+    pub fn exposed_voxels(&self) -> Vec<((i32,i32,i32), Material)> {
+        self.exposed.iter().map(|k| (*k, self.voxels[k])).collect()
+    }
+    fn is_water_voxel(&self, x:i32,y:i32,z:i32) -> bool { self.voxels.get(&(x,y,z)).is_some_and(|m| m.kind == MaterialKind::Water) }
+    // Vóxel de agua sin agua encima: es el que contiene la superficie con olas.
+    fn is_water_top(&self, x:i32,y:i32,z:i32) -> bool { self.is_water_voxel(x,y,z) && !self.is_water_voxel(x,y+1,z) }
+    // Altura de la superficie del agua en (x, z) para el vóxel de superficie de la fila `iy`.
+    fn surface_y(&self, x: f32, z: f32, iy: i32) -> f32 {
+        iy as f32 + 0.5 + self.waves.as_ref().map_or(0.0, |w| w.offset(x, z, self.wave_time))
+    }
+    pub fn water_normal(&self, p: Vec3) -> Vec3 {
+        self.waves.as_ref().map_or(Vec3::new(0.0, 1.0, 0.0), |w| w.normal(p.x, p.z, self.wave_time))
+    }
+    pub fn is_water(&self, p: Vec3) -> bool {
+        let (x, y, z) = ((p.x + 0.5).floor() as i32, (p.y + 0.5).floor() as i32, (p.z + 0.5).floor() as i32);
+        if !self.is_water_voxel(x,y,z) { return false; }
+        // en el vóxel de superficie, por encima de la ola ya es aire
+        !self.is_water_top(x,y,z) || p.y < self.surface_y(p.x, p.z, y)
+    }
+
+    // Primer cruce de la superficie con olas dentro del tramo [t0, t1] del vóxel de superficie.
+    // Desde arriba busca dónde el rayo baja de la ola; desde abajo, dónde la sube.
+    fn water_crossing(&self, ray: &Ray, iy: i32, t0: f32, t1: f32, from_below: bool) -> Option<f32> {
+        let f = |t: f32| {
+            let p = ray.origin + ray.dir * t;
+            let d = p.y - self.surface_y(p.x, p.z, iy);
+            if from_below { -d } else { d }
+        };
+        const STEPS: u32 = 6;
+        let mut a = t0;
+        if f(a) < 0.0 { return Some(t0); }
+        for i in 1..=STEPS {
+            let mut b = t0 + (t1 - t0) * i as f32 / STEPS as f32;
+            if f(b) < 0.0 {
+                for _ in 0..6 {
+                    let m = 0.5 * (a + b);
+                    if f(m) < 0.0 { b = m; } else { a = m; }
+                }
+                return Some(b);
+            }
+            a = b;
+        }
+        None
+    }
+    // Profundidad bajo la superficie del agua en la columna de `p` (0 si no hay agua encima).
+    pub fn water_depth(&self, p: Vec3) -> f32 {
+        let (x, z) = ((p.x + 0.5).floor() as i32, (p.z + 0.5).floor() as i32);
+        let mut y = (p.y + 0.5).floor() as i32;
+        if !self.voxels.get(&(x,y,z)).is_some_and(|m| m.kind == MaterialKind::Water) { y += 1; }
+        let mut top = None;
+        while let Some(m) = self.voxels.get(&(x,y,z)) {
+            if m.kind != MaterialKind::Water { break; }
+            top = Some(y);
+            y += 1;
+        }
+        top.map_or(0.0, |t| (t as f32 + 0.5 - p.y).max(0.0))
+    }
+
+    // Quita los vóxeles de nube del mundo (los dibuja `CloudLayer`) y devuelve sus posiciones.
+    pub fn take_clouds(&mut self) -> Vec<(i32,i32,i32)> {
+        let cells: Vec<(i32,i32,i32)> = self.voxels.iter().filter(|(_k, m)| m.kind == MaterialKind::Cloud).map(|(k,_m)| *k).collect();
+        for c in &cells { self.voxels.remove(c); }
+        self.revision += 1;
+        self.min = (i32::MAX,i32::MAX,i32::MAX);
+        self.max = (i32::MIN,i32::MIN,i32::MIN);
+        for &(x,y,z) in self.voxels.keys() {
+            self.min.0 = self.min.0.min(x); self.min.1 = self.min.1.min(y); self.min.2 = self.min.2.min(z);
+            self.max.0 = self.max.0.max(x); self.max.1 = self.max.1.max(y); self.max.2 = self.max.2.max(z);
+        }
+        self.recompute_exposed();
+        cells
+    }
 
     pub fn enforce_water_border(&mut self, terrain_mat: Material) {
 
@@ -99,7 +207,8 @@ impl VoxelWorld {
                 let (u,v) = if normal.x.abs()>0.9 { ((local.z / h +1.0)*0.5, (local.y / h +1.0)*0.5) }
                              else if normal.y.abs()>0.9 { ((local.x / h +1.0)*0.5, (local.z / h +1.0)*0.5) }
                              else { ((local.x / h +1.0)*0.5, (local.y / h +1.0)*0.5) };
-                return Some(HitInfo { t, position: pos, normal, material: *mat, object_id: ObjectId::Cube, u: u.fract(), v: v.fract() });
+                let (tangent, bitangent) = face_tangents(normal);
+                return Some(HitInfo { t, position: pos, normal, material: *mat, object_id: ObjectId::Cube, u: u.fract(), v: v.fract(), tangent, bitangent });
             }
         }
         None
@@ -108,6 +217,15 @@ impl VoxelWorld {
 
     pub fn occluded(&self, origin: Vec3, dir: Vec3, max_t: f32) -> bool {
         if self.voxels.is_empty() { return false; }
+        // desde fuera de la caja (p. ej. muestras de niebla) se arranca donde el rayo entra
+        let (bb_min, bb_max) = self.aabb_bounds();
+        let inside = origin.x >= bb_min.x && origin.x <= bb_max.x && origin.y >= bb_min.y && origin.y <= bb_max.y && origin.z >= bb_min.z && origin.z <= bb_max.z;
+        let origin = if inside { origin } else {
+            match Self::ray_aabb(&Ray { origin, dir }, bb_min, bb_max) {
+                Some(t) if t < max_t => origin + dir * (t - 1e-3).max(0.0),
+                _ => return false,
+            }
+        };
         let mut ix = (origin.x + 0.5).floor() as i32;
         let mut iy = (origin.y + 0.5).floor() as i32;
         let mut iz = (origin.z + 0.5).floor() as i32;
@@ -147,6 +265,50 @@ impl VoxelWorld {
         false
     }
 
+    // Camino de una sombra que deja pasar luz por materiales translúcidos: None si algo opaco la
+    // corta; si no, cuántos vóxeles translúcidos cruzó (agua y nubes no cuentan).
+    pub fn translucent_path(&self, origin: Vec3, dir: Vec3, max_t: f32) -> Option<u32> {
+        if self.voxels.is_empty() { return Some(0); }
+        let mut ix = (origin.x + 0.5).floor() as i32;
+        let mut iy = (origin.y + 0.5).floor() as i32;
+        let mut iz = (origin.z + 0.5).floor() as i32;
+
+        let step_x = if dir.x > 0.0 { 1 } else { -1 };
+        let step_y = if dir.y > 0.0 { 1 } else { -1 };
+        let step_z = if dir.z > 0.0 { 1 } else { -1 };
+        let invx = if dir.x != 0.0 { 1.0/dir.x } else { f32::INFINITY };
+        let invy = if dir.y != 0.0 { 1.0/dir.y } else { f32::INFINITY };
+        let invz = if dir.z != 0.0 { 1.0/dir.z } else { f32::INFINITY };
+        let next_boundary = |p: f32, i: i32, step: i32| -> f32 { let boundary = i as f32 + 0.5 * step as f32; boundary - p };
+        let mut t_max_x = if invx.is_finite() { next_boundary(origin.x, ix, step_x) * invx } else { f32::INFINITY };
+        let mut t_max_y = if invy.is_finite() { next_boundary(origin.y, iy, step_y) * invy } else { f32::INFINITY };
+        let mut t_max_z = if invz.is_finite() { next_boundary(origin.z, iz, step_z) * invz } else { f32::INFINITY };
+        let t_delta_x = (step_x as f32 * invx).abs();
+        let t_delta_y = (step_y as f32 * invy).abs();
+        let t_delta_z = (step_z as f32 * invz).abs();
+        let mut count = 0;
+        // si el origen ya está dentro de un vóxel translúcido, también cuenta
+        if self.voxels.get(&(ix,iy,iz)).is_some_and(|mat| mat.translucency > 0.0) { count += 1; }
+        for _ in 0..512 {
+            let t_curr;
+            if t_max_x < t_max_y {
+                if t_max_x < t_max_z { ix += step_x; t_curr = t_max_x; t_max_x += t_delta_x; }
+                else { iz += step_z; t_curr = t_max_z; t_max_z += t_delta_z; }
+            } else if t_max_y < t_max_z { iy += step_y; t_curr = t_max_y; t_max_y += t_delta_y; }
+            else { iz += step_z; t_curr = t_max_z; t_max_z += t_delta_z; }
+            if t_curr > max_t { break; }
+            if ix < self.min.0-1 || ix > self.max.0+1 || iy < self.min.1-1 || iy > self.max.1+1 || iz < self.min.2-1 || iz > self.max.2+1 { break; }
+            if let Some(mat) = self.voxels.get(&(ix,iy,iz)) {
+                match mat.kind {
+                    MaterialKind::Water | MaterialKind::Cloud => {}
+                    _ if mat.translucency > 0.0 => count += 1,
+                    _ => return None,
+                }
+            }
+        }
+        Some(count)
+    }
+
   
     pub fn occluded_ignore_water(&self, origin: Vec3, dir: Vec3, max_t: f32) -> bool {
         if self.voxels.is_empty() { return false; }
@@ -187,8 +349,19 @@ impl VoxelWorld {
     }
 }
 
-impl SceneObject for VoxelWorld {
-    fn intersect(&self, ray: &Ray) -> Option<HitInfo> {
+impl VoxelWorld {
+    // Como `intersect`, pero para rayos que arrancan dentro del agua: los vóxeles de agua son
+    // el medio por el que se viaja y el choque es con la cara por la que el rayo sale de él.
+    pub fn intersect_from_water(&self, ray: &Ray) -> Option<HitInfo> { self.traverse(ray, true) }
+
+    fn surface_hit(ray: &Ray, t: f32, material: Material) -> HitInfo {
+        let position = ray.origin + ray.dir * t;
+        let normal = Vec3::new(0.0, 1.0, 0.0);
+        let (tangent, bitangent) = face_tangents(normal);
+        HitInfo { t, position, normal, material, object_id: ObjectId::Cube, u: (position.x + 0.5).rem_euclid(1.0), v: (position.z + 0.5).rem_euclid(1.0), tangent, bitangent }
+    }
+
+    fn traverse(&self, ray: &Ray, from_water: bool) -> Option<HitInfo> {
         if self.voxels.is_empty() { return None; }
         let (bb_min, bb_max) = self.aabb_bounds();
 
@@ -218,19 +391,48 @@ impl SceneObject for VoxelWorld {
         let tDeltaZ = (step_z as f32 * invz).abs();
 
         let max_t = 200.0;
+        let mut last_step = (0, 0, 0);
         for _ in 0..512 { 
             
             if ix < self.min.0-1 || ix > self.max.0+1 || iy < self.min.1-1 || iy > self.max.1+1 || iz < self.min.2-1 || iz > self.max.2+1 { break; }
-            if let Some(hit) = self.voxel_hit(ix,iy,iz, ray) { return Some(hit); }
+            let t_next = tMaxX.min(tMaxY).min(tMaxZ);
+            if from_water {
+                match self.voxels.get(&(ix,iy,iz)) {
+                    Some(m) if m.kind == MaterialKind::Water => {
+                        if self.is_water_top(ix,iy,iz) && let Some(t) = self.water_crossing(ray, iy, t_entry, t_next, true) {
+                            return Some(Self::surface_hit(ray, t, *m));
+                        }
+                    }
+                    Some(_) => { if let Some(hit) = self.voxel_hit(ix,iy,iz, ray) { return Some(hit); } }
+                    None => {
+                        // salió del agua: la normal apunta hacia afuera, en la dirección del último paso
+                        let water = self.voxels.get(&(ix - last_step.0, iy - last_step.1, iz - last_step.2)).copied()?;
+                        let normal = Vec3::new(last_step.0 as f32, last_step.1 as f32, last_step.2 as f32);
+                        let mut pos = ray.origin + ray.dir * t_entry;
+                        if last_step.1 > 0 { pos.y = iy as f32 - 0.5 + 1e-4; }
+                        let (tangent, bitangent) = face_tangents(normal);
+                        return Some(HitInfo { t: t_entry, position: pos, normal, material: water, object_id: ObjectId::Cube, u: (pos.x + 0.5).rem_euclid(1.0), v: (pos.z + 0.5).rem_euclid(1.0), tangent, bitangent });
+                    }
+                }
+            } else if self.is_water_top(ix,iy,iz) {
+                // la ola está dentro del vóxel: si el rayo no la cruza aquí, sigue de largo
+                if let Some(t) = self.water_crossing(ray, iy, t_entry, t_next, false) {
+                    return Some(Self::surface_hit(ray, t, self.voxels[&(ix,iy,iz)]));
+                }
+            } else if let Some(hit) = self.voxel_hit(ix,iy,iz, ray) { return Some(hit); }
             if tMaxX < tMaxY {
-                if tMaxX < tMaxZ { ix += step_x; t_entry = tMaxX; tMaxX += tDeltaX; }
-                else { iz += step_z; t_entry = tMaxZ; tMaxZ += tDeltaZ; }
+                if tMaxX < tMaxZ { ix += step_x; t_entry = tMaxX; tMaxX += tDeltaX; last_step = (step_x, 0, 0); }
+                else { iz += step_z; t_entry = tMaxZ; tMaxZ += tDeltaZ; last_step = (0, 0, step_z); }
             } else {
-                if tMaxY < tMaxZ { iy += step_y; t_entry = tMaxY; tMaxY += tDeltaY; }
-                else { iz += step_z; t_entry = tMaxZ; tMaxZ += tDeltaZ; }
+                if tMaxY < tMaxZ { iy += step_y; t_entry = tMaxY; tMaxY += tDeltaY; last_step = (0, step_y, 0); }
+                else { iz += step_z; t_entry = tMaxZ; tMaxZ += tDeltaZ; last_step = (0, 0, step_z); }
             }
             if t_entry > max_t { break; }
         }
         None
     }
 }
+
+impl SceneObject for VoxelWorld {
+    fn intersect(&self, ray: &Ray) -> Option<HitInfo> { self.traverse(ray, false) }
+}
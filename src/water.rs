@@ -0,0 +1,129 @@
+use crate::color::{Color, Vec3};
+use crate::config::Config;
+
+// Absorción por vóxel recorrido: el rojo se apaga primero, por eso el fondo se ve verde azulado.
+const ABSORPTION: Color = Color { r: 0.45, g: 0.12, b: 0.08 };
+const SCATTER_TINT: Color = Color { r: 0.05, g: 0.2, b: 0.24 };
+
+// El agua como medio participante: absorción de Beer–Lambert por distancia real recorrida
+// y una niebla que dispersa la luz del cielo y del sol dentro del estanque.
+pub struct WaterMedium {
+    absorption: Color,
+    scattering: f32,
+}
+
+impl WaterMedium {
+    pub fn from_config(cfg: &Config) -> Self {
+        Self {
+            absorption: ABSORPTION * cfg.get_f32("water_absorption", 1.0).max(0.0),
+            scattering: cfg.get_f32("water_fog", 0.35).max(0.0),
+        }
+    }
+
+    // Fracción de luz que sobrevive a `dist` vóxeles de agua.
+    pub fn transmittance(&self, dist: f32) -> Color {
+        let e = |sigma: f32| (-(sigma + self.scattering) * dist).exp();
+        Color::new(e(self.absorption.r), e(self.absorption.g), e(self.absorption.b))
+    }
+
+    // `color` visto a través de `dist` vóxeles de agua. `light` es la luz que llega a la
+    // superficie (cielo + sol) y alimenta la niebla submarina.
+    pub fn apply(&self, color: Color, dist: f32, light: Color) -> Color {
+        let t = self.transmittance(dist.min(1e4));
+        let fog = 1.0 - (-self.scattering * dist.min(1e4)).exp();
+        color * t + SCATTER_TINT * light * fog
+    }
+
+    // Luz directa que llega a un punto a `depth` vóxeles bajo la superficie.
+    pub fn light_at_depth(&self, depth: f32, to_light: Vec3) -> Color {
+        if depth <= 0.0 { return Color::white(); }
+        self.transmittance(depth / to_light.y.max(0.2))
+    }
+}
+
+struct Wave {
+    dir: (f32, f32), // dirección de avance en xz, unitaria
+    k: f32,          // número de onda 2π/λ
+    omega: f32,      // frecuencia angular (dispersión de aguas profundas)
+    amplitude: f32,
+    steepness: f32,  // Q de Gerstner: 0 = senoidal, 1 = crestas en punta
+    phase: f32,
+}
+
+// Superficie del agua como suma de ondas de Gerstner. La altura se mide hacia abajo desde la
+// cara superior del vóxel (entre -2A y 0) para que la superficie nunca salga de su vóxel.
+pub struct Waves {
+    waves: Vec<Wave>,
+    total_amplitude: f32,
+}
+
+impl Waves {
+    pub fn from_config(cfg: &Config) -> Self {
+        // (longitud de onda, amplitud, giro respecto del viento en grados)
+        let (table, steepness): (&[(f32, f32, f32)], f32) = match cfg.get_str("waves").unwrap_or("calm") {
+            "off" | "none" | "false" => (&[], 0.0),
+            "windy" => (&[(4.8, 0.045, 0.0), (3.1, 0.035, 25.0), (2.2, 0.025, -30.0), (1.5, 0.018, 55.0), (1.1, 0.012, -65.0), (0.7, 0.008, 10.0)], 0.7),
+            other => {
+                if other != "calm" { eprintln!("[waves] unknown preset '{}', using calm", other); }
+                (&[(2.3, 0.014, 0.0), (1.6, 0.01, 70.0), (1.1, 0.007, -110.0), (0.6, 0.004, 160.0)], 0.35)
+            }
+        };
+        let scale = cfg.get_f32("wave_scale", 1.0).max(0.0);
+        let wind = cfg.get_f32("wave_direction", 30.0);
+        let n = table.len().max(1) as f32;
+        let waves: Vec<Wave> = table.iter().enumerate().map(|(i, &(length, amplitude, turn))| {
+            let a = (wind + turn).to_radians();
+            let k = std::f32::consts::TAU / length;
+            let amplitude = amplitude * scale;
+            Wave {
+                dir: (a.cos(), a.sin()), k, omega: (9.8 * k).sqrt(), amplitude,
+                steepness: if amplitude > 0.0 { (steepness / (k * amplitude * n)).min(1.0) } else { 0.0 },
+                phase: i as f32 * 2.39996,
+            }
+        }).collect();
+        let total_amplitude = waves.iter().map(|w| w.amplitude).sum::<f32>().min(0.24);
+        Self { waves, total_amplitude }
+    }
+
+    pub fn is_active(&self) -> bool { !self.waves.is_empty() }
+
+    fn theta(w: &Wave, x: f32, z: f32, time: f32) -> f32 { w.k * (w.dir.0 * x + w.dir.1 * z) - w.omega * time + w.phase }
+
+    // Gerstner desplaza los puntos en horizontal: busca el punto de reposo que cae en (x, z).
+    fn rest_position(&self, x: f32, z: f32, time: f32) -> (f32, f32) {
+        let (mut x0, mut z0) = (x, z);
+        for _ in 0..3 {
+            let (mut dx, mut dz) = (0.0, 0.0);
+            for w in &self.waves {
+                let c = Self::theta(w, x0, z0, time).cos() * w.steepness * w.amplitude;
+                dx += w.dir.0 * c;
+                dz += w.dir.1 * c;
+            }
+            x0 = x - dx;
+            z0 = z - dz;
+        }
+        (x0, z0)
+    }
+
+    // Desplazamiento vertical respecto de la cara superior del vóxel de agua (siempre <= 0).
+    pub fn offset(&self, x: f32, z: f32, time: f32) -> f32 {
+        if self.waves.is_empty() { return 0.0; }
+        let (x0, z0) = self.rest_position(x, z, time);
+        let h: f32 = self.waves.iter().map(|w| w.amplitude * Self::theta(w, x0, z0, time).sin()).sum();
+        h.clamp(-self.total_amplitude, self.total_amplitude) - self.total_amplitude
+    }
+
+    pub fn normal(&self, x: f32, z: f32, time: f32) -> Vec3 {
+        if self.waves.is_empty() { return Vec3::new(0.0, 1.0, 0.0); }
+        let (x0, z0) = self.rest_position(x, z, time);
+        let (mut nx, mut ny, mut nz) = (0.0, 1.0, 0.0);
+        for w in &self.waves {
+            let th = Self::theta(w, x0, z0, time);
+            let wa = w.k * w.amplitude;
+            nx -= w.dir.0 * wa * th.cos();
+            nz -= w.dir.1 * wa * th.cos();
+            ny -= w.steepness * wa * th.sin();
+        }
+        Vec3::new(nx, ny, nz).normalized()
+    }
+}